@@ -0,0 +1,61 @@
+//! Rules for user-chosen keys ("vanity aliases") such as `/spring-sale`.
+
+use crate::error::ApiError;
+
+pub const MIN_ALIAS_LEN: usize = 3;
+pub const MAX_ALIAS_LEN: usize = 64;
+
+/// Paths the server routes itself, or may in future; an alias must not shadow them.
+const RESERVED_ALIASES: &[&str] = &[
+    "shorten-and-retrieve-url",
+    "retrieve-original-url",
+    "top-urls",
+    "admin",
+    "api",
+    "batch",
+    "events",
+    "health",
+    "metrics",
+    "redirect",
+    "resolve",
+    "static",
+];
+
+/// Checks length, charset (ASCII letters, digits, `-` and `_`) and reserved words.
+pub fn validate_alias(alias: &str) -> Result<(), ApiError> {
+    if alias.len() < MIN_ALIAS_LEN || alias.len() > MAX_ALIAS_LEN {
+        return Err(ApiError::InvalidAlias(format!(
+            "must be between {} and {} characters long",
+            MIN_ALIAS_LEN, MAX_ALIAS_LEN
+        )));
+    }
+    if !alias.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_') {
+        return Err(ApiError::InvalidAlias(
+            "may only contain letters, digits, '-' and '_'".to_string(),
+        ));
+    }
+    if RESERVED_ALIASES.iter().any(|reserved| reserved.eq_ignore_ascii_case(alias)) {
+        return Err(ApiError::InvalidAlias(format!("{} is reserved", alias)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_readable_aliases() {
+        assert!(validate_alias("spring-sale").is_ok());
+        assert!(validate_alias("Q3_launch").is_ok());
+    }
+
+    #[test]
+    fn rejects_bad_aliases() {
+        assert!(validate_alias("ab").is_err());
+        assert!(validate_alias(&"a".repeat(MAX_ALIAS_LEN + 1)).is_err());
+        assert!(validate_alias("spring sale").is_err());
+        assert!(validate_alias("sale/2024").is_err());
+        assert!(validate_alias("Top-URLs").is_err());
+    }
+}
@@ -0,0 +1,752 @@
+//! Versioned REST resource for links, under `/api/v1/links`.
+//!
+//! Every endpoint answers with the same [`LinkResource`] shape. Reads here
+//! never count as resolutions; only redirects and the legacy lookup route do.
+
+use std::mem;
+
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument};
+
+use crate::error::{ApiError, ErrorBody};
+use crate::handlers::{create_alias_link, lookup_link, shorten_link, Lookup};
+use crate::settings::Settings;
+use crate::store::{Counter, Link, LinkQuery, LinkSort, LinkStore, LinkUpdate, RedirectCode, StoreError};
+use crate::validate::{validate_owner, validate_tags, validate_url};
+
+/// A link as returned by every `/api/v1/links` endpoint.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LinkResource {
+    pub key: String,
+    pub url: String,
+    /// Path that redirects to `url`, relative to where the shortener is mounted.
+    pub path: String,
+    /// The status redirects use, whether the link's own or the server default.
+    pub redirect_code: RedirectCode,
+    pub tags: Vec<String>,
+    pub owner: Option<String>,
+    pub created_at: u64,
+    pub expires_at: Option<u64>,
+    pub creations: u64,
+    pub resolutions: u64,
+    pub redirects: u64,
+}
+
+impl LinkResource {
+    pub fn new(link: Link, settings: &Settings) -> LinkResource {
+        LinkResource {
+            path: format!("{}/{}", settings.redirect_prefix, link.key),
+            redirect_code: settings.redirect_code_for(&link),
+            key: link.key,
+            url: link.url,
+            tags: link.tags,
+            owner: link.owner,
+            created_at: link.created_at,
+            expires_at: link.expires_at,
+            creations: link.creations,
+            resolutions: link.resolutions,
+            redirects: link.redirects,
+        }
+    }
+}
+
+/// Body of `POST /api/v1/links`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NewLink {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redirect_code: Option<RedirectCode>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LinkList {
+    pub links: Vec<LinkResource>,
+    /// Pass as `cursor` to fetch the next page; absent on the last page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// Outcome of one item of `POST /api/v1/links/batch`, in request order.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BatchResult {
+    pub index: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<LinkResource>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorBody>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BatchResponse {
+    pub succeeded: usize,
+    pub failed: usize,
+    pub results: Vec<BatchResult>,
+}
+
+/// Body of `POST /api/v1/links/resolve`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ResolveRequest {
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkStatus {
+    Active,
+    Expired,
+    Deleted,
+    Unknown,
+}
+
+/// What one key of `POST /api/v1/links/resolve` points at, in request order.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ResolvedKey {
+    pub key: String,
+    pub status: LinkStatus,
+    /// The stored link, for active and expired keys.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<LinkResource>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ResolveResponse {
+    pub results: Vec<ResolvedKey>,
+}
+
+/// Content types read as one JSON item per line.
+const NDJSON_TYPES: &[&str] = &["application/x-ndjson", "application/ndjson", "application/jsonl"];
+
+/// Upper bounds on the size of `POST /api/v1/links/batch`; the item limit
+/// also caps the keys of `POST /api/v1/links/resolve`.
+const MAX_BATCH_ITEMS: usize = 10_000;
+const MAX_BATCH_BYTES: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct ListQuery {
+    #[serde(default)]
+    sort: LinkSort,
+    cursor: Option<String>,
+    #[serde(default = "default_list_limit")]
+    limit: usize,
+    /// Only links whose destination host is this domain.
+    domain: Option<String>,
+    tag: Option<String>,
+    owner: Option<String>,
+    /// Only links created at or after this Unix time.
+    since: Option<u64>,
+    /// Only links created before this Unix time.
+    until: Option<u64>,
+}
+
+fn default_list_limit() -> usize {
+    50
+}
+
+/// Upper bound on `limit` for `GET /api/v1/links`.
+const MAX_LIST_LIMIT: usize = 1000;
+
+impl ListQuery {
+    /// Fetches the requested page, and the cursor of the next one if any.
+    ///
+    /// The cursor is the sort value and key of the last link on the page; one
+    /// link more than the page holds is asked for to learn whether it is the last.
+    fn page(&self, store: &dyn LinkStore) -> Result<(Vec<Link>, Option<String>), ApiError> {
+        let limit = self.limit.clamp(1, MAX_LIST_LIMIT);
+        let non_empty = |filter: &Option<String>| filter.clone().filter(|filter| !filter.is_empty());
+        let query = LinkQuery {
+            sort: self.sort,
+            after: self.cursor.as_deref().map(decode_cursor).transpose()?,
+            limit: limit + 1,
+            domain: non_empty(&self.domain).map(|domain| domain.trim_end_matches('.').to_ascii_lowercase()),
+            tag: non_empty(&self.tag),
+            owner: non_empty(&self.owner),
+            since: self.since.map(|since| stored_time("since", since)).transpose()?,
+            until: self.until.map(|until| stored_time("until", until)).transpose()?,
+        };
+
+        let mut links = store.list_page(&query)?;
+        if links.len() <= limit {
+            return Ok((links, None));
+        }
+        links.truncate(limit);
+        let next_cursor = links.last().map(|last| format!("{}:{}", query.sort_value(last), last.key));
+        Ok((links, next_cursor))
+    }
+}
+
+fn decode_cursor(cursor: &str) -> Result<(u64, String), ApiError> {
+    cursor
+        .split_once(':')
+        .and_then(|(value, key)| Some((value.parse().ok()?, key.to_string())))
+        .filter(|(value, _)| i64::try_from(*value).is_ok())
+        .ok_or_else(|| ApiError::InvalidQuery(format!("invalid cursor: {}", cursor)))
+}
+
+/// Checks that a Unix time fits the signed integers SQLite stores, so every
+/// store answers a filter on it the same way.
+fn stored_time(name: &str, value: u64) -> Result<u64, ApiError> {
+    i64::try_from(value)
+        .map(|_| value)
+        .map_err(|_| ApiError::InvalidQuery(format!("{} is out of range: {}", name, value)))
+}
+
+/// Registers the `/api/v1` routes.
+pub(crate) fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/v1")
+            .service(
+                web::resource("/links")
+                    .route(web::post().to(create_link))
+                    .route(web::get().to(list_links)),
+            )
+            .service(
+                web::resource("/links/batch")
+                    .app_data(web::PayloadConfig::new(MAX_BATCH_BYTES))
+                    .route(web::post().to(create_links)),
+            )
+            .service(web::resource("/links/resolve").route(web::post().to(resolve_links)))
+            .service(
+                web::resource("/links/{key}")
+                    .route(web::get().to(get_link))
+                    .route(web::patch().to(update_link))
+                    .route(web::delete().to(delete_link)),
+            ),
+    );
+}
+
+// Handler function for creating a link; an already shortened URL returns its existing link with 200
+#[instrument(skip_all)]
+async fn create_link(
+    req: HttpRequest,
+    store: web::Data<dyn LinkStore>,
+    settings: web::Data<Settings>,
+    body: web::Json<NewLink>,
+) -> Result<HttpResponse, ApiError> {
+    let (link, created) = new_link(store.get_ref(), &settings, &body)?;
+    if !created {
+        return Ok(HttpResponse::Ok().json(LinkResource::new(link, &settings)));
+    }
+    let location = format!("{}/{}", req.path().trim_end_matches('/'), link.key);
+    Ok(HttpResponse::Created()
+        .append_header((header::LOCATION, location))
+        .json(LinkResource::new(link, &settings)))
+}
+
+/// Validates and stores one requested link, for both the single and the batch
+/// endpoint, returning it and whether it was created.
+///
+/// A URL that is already shortened gets its existing link back untouched, so
+/// nobody can change someone else's link by submitting the same URL; asking
+/// for different settings than it has is a conflict instead.
+fn new_link(store: &dyn LinkStore, settings: &Settings, new: &NewLink) -> Result<(Link, bool), ApiError> {
+    let url = validate_url(&new.url, settings)?;
+    let link = Link {
+        redirect_code: new.redirect_code,
+        tags: validate_tags(&new.tags)?,
+        owner: new.owner.as_deref().map(validate_owner).transpose()?,
+        expires_at: new.expires_at,
+        ..Link::new("", url)
+    };
+
+    if let Some(alias) = &new.alias {
+        return Ok((create_alias_link(store, Link { key: alias.clone(), ..link })?, true));
+    }
+    let (existing, created) = shorten_link(store, settings, link.clone())?;
+    if created {
+        return Ok((existing, true));
+    }
+    let conflicts = (link.redirect_code.is_some() && link.redirect_code != existing.redirect_code)
+        || (!link.tags.is_empty() && link.tags != existing.tags)
+        || (link.owner.is_some() && link.owner != existing.owner)
+        || (link.expires_at.is_some() && link.expires_at != existing.expires_at);
+    if conflicts {
+        return Err(ApiError::LinkExists(existing.key));
+    }
+    let creations = store.increment(&existing.key, Counter::Creations)?.unwrap_or(existing.creations);
+    Ok((Link { creations, ..existing }, false))
+}
+
+/// Reads a batch body: a JSON array, or one item per line when sent as NDJSON.
+/// An item is either a bare URL or a [`NewLink`]; items that do not parse
+/// become per-item errors rather than failing the batch.
+fn parse_batch(req: &HttpRequest, body: &[u8]) -> Result<Vec<Result<NewLink, ApiError>>, ApiError> {
+    let ndjson = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| NDJSON_TYPES.contains(&mime.trim()));
+    let values: Vec<serde_json::Result<serde_json::Value>> = if ndjson {
+        let body = std::str::from_utf8(body).map_err(|err| ApiError::InvalidBody(err.to_string()))?;
+        body.lines().filter(|line| !line.trim().is_empty()).map(serde_json::from_str).collect()
+    } else {
+        let values: Vec<serde_json::Value> =
+            serde_json::from_slice(body).map_err(|err| ApiError::InvalidBody(err.to_string()))?;
+        values.into_iter().map(Ok).collect()
+    };
+    if values.len() > MAX_BATCH_ITEMS {
+        return Err(ApiError::InvalidBody(format!("at most {} items per batch", MAX_BATCH_ITEMS)));
+    }
+
+    let item = |value: serde_json::Result<serde_json::Value>| match value? {
+        serde_json::Value::String(url) => Ok(NewLink {
+            url,
+            alias: None,
+            redirect_code: None,
+            tags: Vec::new(),
+            owner: None,
+            expires_at: None,
+        }),
+        value => serde_json::from_value(value),
+    };
+    Ok(values
+        .into_iter()
+        .map(|value| item(value).map_err(|err| ApiError::InvalidBody(err.to_string())))
+        .collect())
+}
+
+// Handler function for shortening many URLs in one request, in one store transaction where supported
+#[instrument(skip_all)]
+async fn create_links(
+    req: HttpRequest,
+    store: web::Data<dyn LinkStore>,
+    settings: web::Data<Settings>,
+    body: web::Bytes,
+) -> Result<HttpResponse, ApiError> {
+    let mut items = parse_batch(&req, &body)?;
+    let mut outcomes = Vec::with_capacity(items.len());
+    store.transaction(&mut |tx| {
+        for item in mem::take(&mut items) {
+            let outcome = item.and_then(|new| new_link(tx, &settings, &new)).map(|(link, _)| link);
+            // Only a failing backend aborts the batch; everything else is the item's own problem.
+            if let Err(ApiError::Store(StoreError::Backend(reason))) = &outcome {
+                return Err(StoreError::Backend(reason.clone()));
+            }
+            outcomes.push(outcome);
+        }
+        Ok(())
+    })?;
+
+    let results: Vec<BatchResult> = outcomes
+        .into_iter()
+        .enumerate()
+        .map(|(index, outcome)| match outcome {
+            Ok(link) => BatchResult { index, link: Some(LinkResource::new(link, &settings)), error: None },
+            Err(err) => BatchResult { index, link: None, error: Some(ErrorBody::from(&err)) },
+        })
+        .collect();
+    let failed = results.iter().filter(|result| result.error.is_some()).count();
+    info!(items = results.len(), failed, "batch shortened");
+    Ok(HttpResponse::Ok().json(BatchResponse { succeeded: results.len() - failed, failed, results }))
+}
+
+// Handler function for expanding many keys at once; nothing is counted
+#[instrument(skip_all)]
+async fn resolve_links(
+    store: web::Data<dyn LinkStore>,
+    settings: web::Data<Settings>,
+    body: web::Json<ResolveRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let keys = body.into_inner().keys;
+    if keys.len() > MAX_BATCH_ITEMS {
+        return Err(ApiError::InvalidBody(format!("at most {} keys per request", MAX_BATCH_ITEMS)));
+    }
+
+    let mut results = Vec::with_capacity(keys.len());
+    for key in keys {
+        let (status, link, deleted_at) = match lookup_link(store.get_ref(), &key)? {
+            Lookup::Active(link) => (LinkStatus::Active, Some(link), None),
+            Lookup::Expired(link) => (LinkStatus::Expired, Some(link), None),
+            Lookup::Deleted(at) => (LinkStatus::Deleted, None, Some(at)),
+            Lookup::Unknown => (LinkStatus::Unknown, None, None),
+        };
+        let link = link.map(|link| LinkResource::new(link, &settings));
+        results.push(ResolvedKey { key, status, link, deleted_at });
+    }
+    Ok(HttpResponse::Ok().json(ResolveResponse { results }))
+}
+
+// Handler function for listing links a page at a time, filtered and sorted as asked
+#[instrument(skip_all)]
+async fn list_links(
+    store: web::Data<dyn LinkStore>,
+    settings: web::Data<Settings>,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let (links, next_cursor) = query.page(store.get_ref())?;
+    let links = links.into_iter().map(|link| LinkResource::new(link, &settings)).collect();
+    Ok(HttpResponse::Ok().json(LinkList { links, next_cursor }))
+}
+
+// Handler function for inspecting one link
+#[instrument(skip_all, fields(key = %key))]
+async fn get_link(
+    store: web::Data<dyn LinkStore>,
+    settings: web::Data<Settings>,
+    key: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let key = key.into_inner();
+    let link = store.resolve(&key)?.ok_or(ApiError::NotFound(key))?;
+    Ok(HttpResponse::Ok().json(LinkResource::new(link, &settings)))
+}
+
+// Handler function for changing a link's destination, redirect code, tags or owner
+#[instrument(skip_all, fields(key = %key))]
+async fn update_link(
+    store: web::Data<dyn LinkStore>,
+    settings: web::Data<Settings>,
+    key: web::Path<String>,
+    body: web::Json<LinkUpdate>,
+) -> Result<HttpResponse, ApiError> {
+    let key = key.into_inner();
+    let mut update = body.into_inner();
+    if let Some(url) = &update.url {
+        update.url = Some(validate_url(url, &settings)?.to_string());
+    }
+    if let Some(tags) = &update.tags {
+        update.tags = Some(validate_tags(tags)?);
+    }
+    if let Some(Some(owner)) = &update.owner {
+        update.owner = Some(Some(validate_owner(owner)?));
+    }
+
+    let link = store.update(&key, update)?.ok_or(ApiError::NotFound(key))?;
+    info!(key = %link.key, "link updated");
+    Ok(HttpResponse::Ok().json(LinkResource::new(link, &settings)))
+}
+
+// Handler function for removing a link
+#[instrument(skip_all, fields(key = %key))]
+async fn delete_link(store: web::Data<dyn LinkStore>, key: web::Path<String>) -> Result<HttpResponse, ApiError> {
+    let key = key.into_inner();
+    if !store.delete(&key)? {
+        return Err(ApiError::NotFound(key));
+    }
+    info!(%key, "link deleted");
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use actix_web::{test as actix_test, App};
+    use serde_json::json;
+
+    use crate::server::service;
+    use crate::store::{MemoryStore, SqliteStore};
+
+    #[actix_rt::test]
+    async fn test_link_lifecycle() {
+        let store: Arc<dyn LinkStore> = Arc::new(MemoryStore::new());
+        let app = actix_test::init_service(App::new().configure(service(store.clone(), Settings::default()))).await;
+
+        let req = actix_test::TestRequest::post()
+            .uri("/api/v1/links")
+            .set_json(json!({ "url": "https://coderprog.com", "tags": ["promo", "launch"], "owner": "growth" }))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 201);
+        assert_eq!(resp.headers().get(header::LOCATION).unwrap(), "/api/v1/links/844c01eb2e56");
+        let link: LinkResource = actix_test::read_body_json(resp).await;
+        assert_eq!((link.key.as_str(), link.path.as_str()), ("844c01eb2e56", "/844c01eb2e56"));
+        assert_eq!(link.tags, ["launch", "promo"]);
+        assert_eq!(link.owner.as_deref(), Some("growth"));
+
+        let req = actix_test::TestRequest::patch()
+            .uri("/api/v1/links/844c01eb2e56")
+            .set_json(json!({ "url": "https://coderprog.com/new", "redirect_code": 301, "owner": null }))
+            .to_request();
+        let link: LinkResource = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(link.url, "https://coderprog.com/new");
+        assert_eq!(link.redirect_code, RedirectCode::MovedPermanently);
+        assert_eq!((link.owner, link.tags.len()), (None, 2));
+
+        let req = actix_test::TestRequest::get().uri("/api/v1/links/844c01eb2e56").to_request();
+        let link: LinkResource = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!((link.url.as_str(), link.resolutions), ("https://coderprog.com/new", 0));
+
+        let req = actix_test::TestRequest::get().uri("/api/v1/links").to_request();
+        let list: LinkList = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(list.links.len(), 1);
+
+        let req = actix_test::TestRequest::delete().uri("/api/v1/links/844c01eb2e56").to_request();
+        assert_eq!(actix_test::call_service(&app, req).await.status(), 204);
+        let req = actix_test::TestRequest::delete().uri("/api/v1/links/844c01eb2e56").to_request();
+        assert_eq!(actix_test::call_service(&app, req).await.status(), 404);
+        let req = actix_test::TestRequest::get().uri("/api/v1/links/844c01eb2e56").to_request();
+        assert_eq!(actix_test::call_service(&app, req).await.status(), 404);
+    }
+
+    fn link(key: &str, url: &str, created_at: u64, redirects: u64) -> Link {
+        Link { created_at, redirects, ..Link::new(key, url) }
+    }
+
+    fn keys(links: &[Link]) -> Vec<&str> {
+        links.iter().map(|link| link.key.as_str()).collect()
+    }
+
+    /// Both kinds of `list_page`: the default over `list` and SQLite's own query.
+    fn stores_with(links: &[Link]) -> Vec<Box<dyn LinkStore>> {
+        let stores: Vec<Box<dyn LinkStore>> = vec![Box::new(MemoryStore::new()), Box::new(SqliteStore::open_in_memory().unwrap())];
+        for store in &stores {
+            for link in links {
+                store.create(link.clone()).unwrap();
+            }
+        }
+        stores
+    }
+
+    #[test]
+    fn test_pages_through_links_with_a_cursor() {
+        let links = [
+            link("d", "https://a.example/4", 30, 1),
+            link("a", "https://a.example/1", 10, 5),
+            link("c", "https://a.example/3", 20, 5),
+            link("b", "https://a.example/2", 20, 0),
+        ];
+        for store in stores_with(&links) {
+            let store = store.as_ref();
+            let mut query = ListQuery { limit: 3, ..ListQuery::default() };
+            let (page, cursor) = query.page(store).unwrap();
+            assert_eq!((keys(&page), cursor.as_deref()), (vec!["a", "b", "c"], Some("20:c")));
+            query.cursor = cursor;
+            let (page, cursor) = query.page(store).unwrap();
+            assert_eq!((keys(&page), cursor), (vec!["d"], None));
+
+            let mut query = ListQuery { sort: LinkSort::Clicks, limit: 2, ..ListQuery::default() };
+            let (page, cursor) = query.page(store).unwrap();
+            assert_eq!((keys(&page), cursor.as_deref()), (vec!["a", "c"], Some("5:c")));
+            query.cursor = cursor;
+            let (page, cursor) = query.page(store).unwrap();
+            assert_eq!((keys(&page), cursor), (vec!["d", "b"], None));
+
+            query.cursor = Some("c".to_string());
+            assert!(matches!(query.page(store), Err(ApiError::InvalidQuery(_))));
+        }
+    }
+
+    #[test]
+    fn test_filters_links() {
+        let mut tagged = link("a", "https://Docs.Example.com/x", 10, 0);
+        tagged.tags = vec!["promo".to_string(), "x".to_string()];
+        tagged.owner = Some("growth".to_string());
+        let links = [tagged, link("b", "https://example.com", 20, 0), link("c", "https://docs.example.com", 30, 0)];
+
+        for store in stores_with(&links) {
+            let filter = |query: ListQuery| keys(&query.page(store.as_ref()).unwrap().0).join(",");
+            let query = |domain: Option<&str>, tag: Option<&str>, owner: Option<&str>, since, until| ListQuery {
+                domain: domain.map(str::to_string),
+                tag: tag.map(str::to_string),
+                owner: owner.map(str::to_string),
+                since,
+                until,
+                limit: 50,
+                ..ListQuery::default()
+            };
+            assert_eq!(filter(query(Some("DOCS.example.com."), None, None, None, None)), "a,c");
+            assert_eq!(filter(query(None, Some("promo"), None, None, None)), "a");
+            assert_eq!(filter(query(None, Some("prom"), None, None, None)), "");
+            assert_eq!(filter(query(None, None, Some("growth"), None, None)), "a");
+            assert_eq!(filter(query(None, None, None, Some(20), Some(30))), "b");
+            assert_eq!(filter(query(Some("example.com"), Some("promo"), None, None, None)), "");
+            assert_eq!(filter(query(Some(""), None, Some(""), None, None)), "a,b,c");
+            for out_of_range in [query(None, None, None, Some(u64::MAX), None), query(None, None, None, None, Some(u64::MAX))] {
+                assert!(matches!(out_of_range.page(store.as_ref()), Err(ApiError::InvalidQuery(_))));
+            }
+            let past_the_end = ListQuery { cursor: Some(format!("{}:a", u64::MAX)), ..query(None, None, None, None, None) };
+            assert!(matches!(past_the_end.page(store.as_ref()), Err(ApiError::InvalidQuery(_))));
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_list_query_string() {
+        let store: Arc<dyn LinkStore> = Arc::new(MemoryStore::new());
+        store.create(link("abc", "https://coderprog.com", 10, 0)).unwrap();
+        store.create(link("def", "https://example.com", 20, 0)).unwrap();
+        let app = actix_test::init_service(App::new().configure(service(store, Settings::default()))).await;
+
+        let req = actix_test::TestRequest::get().uri("/api/v1/links?sort=created&limit=1").to_request();
+        let list: LinkList = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!((list.links[0].key.as_str(), list.next_cursor.as_deref()), ("abc", Some("10:abc")));
+
+        let req = actix_test::TestRequest::get().uri("/api/v1/links?domain=example.com&since=15").to_request();
+        let list: LinkList = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!((list.links.len(), list.next_cursor), (1, None));
+
+        let req = actix_test::TestRequest::get().uri("/api/v1/links?sort=oldest").to_request();
+        assert_eq!(actix_test::call_service(&app, req).await.status(), 400);
+    }
+
+    #[actix_rt::test]
+    async fn test_resubmitting_a_url_keeps_the_existing_link() {
+        let store: Arc<dyn LinkStore> = Arc::new(MemoryStore::new());
+        let app = actix_test::init_service(App::new().configure(service(store.clone(), Settings::default()))).await;
+        let post = |body: serde_json::Value| actix_test::TestRequest::post().uri("/api/v1/links").set_json(body).to_request();
+
+        let resp = actix_test::call_service(&app, post(json!({ "url": "https://coderprog.com", "owner": "alice" }))).await;
+        assert_eq!(resp.status(), 201);
+
+        for body in [
+            json!({ "url": "https://coderprog.com/", "owner": "bob" }),
+            json!({ "url": "https://coderprog.com/", "tags": ["takeover"] }),
+            json!({ "url": "https://coderprog.com/", "expires_at": 1 }),
+            json!({ "url": "https://coderprog.com/", "redirect_code": 301 }),
+        ] {
+            let resp = actix_test::call_service(&app, post(body.clone())).await;
+            assert_eq!(resp.status(), 409, "{}", body);
+            let error: serde_json::Value = actix_test::read_body_json(resp).await;
+            assert_eq!(error["code"], "link_exists");
+        }
+
+        let resp = actix_test::call_service(&app, post(json!({ "url": "https://coderprog.com/", "owner": "alice" }))).await;
+        assert_eq!(resp.status(), 200);
+        assert!(resp.headers().get(header::LOCATION).is_none());
+        let link: LinkResource = actix_test::read_body_json(resp).await;
+        assert_eq!(link.creations, 2);
+
+        let resp = actix_test::call_service(&app, post(json!({ "url": "https://coderprog.com" }))).await;
+        assert_eq!(resp.status(), 200);
+        let stored = store.resolve("844c01eb2e56").unwrap().unwrap();
+        assert_eq!((stored.owner.as_deref(), stored.tags.len(), stored.expires_at), (Some("alice"), 0, None));
+        assert_eq!((stored.redirect_code, stored.creations), (None, 3));
+    }
+
+    #[actix_rt::test]
+    async fn test_batch_reports_each_item() {
+        let store: Arc<dyn LinkStore> = Arc::new(MemoryStore::new());
+        let app = actix_test::init_service(App::new().configure(service(store.clone(), Settings::default()))).await;
+
+        let req = actix_test::TestRequest::post()
+            .uri("/api/v1/links/batch")
+            .set_json(json!([
+                "https://coderprog.com",
+                { "url": "https://coderprog.com/spring", "alias": "spring-sale", "tags": ["promo"] },
+                { "url": "https://coderprog.com/fall", "alias": "spring-sale" },
+                "ftp://coderprog.com",
+                5,
+            ]))
+            .to_request();
+        let batch: BatchResponse = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!((batch.succeeded, batch.failed), (2, 3));
+        let codes: Vec<_> = batch.results.iter().map(|r| r.error.as_ref().map(|e| e.code.as_str())).collect();
+        assert_eq!(codes, [None, None, Some("alias_taken"), Some("unsupported_scheme"), Some("invalid_body")]);
+        assert_eq!(batch.results[1].link.as_ref().unwrap().tags, ["promo"]);
+        assert_eq!(batch.results[4].index, 4);
+        assert_eq!(store.len().unwrap(), 2);
+
+        let req = actix_test::TestRequest::post()
+            .uri("/api/v1/links/batch")
+            .set_json(json!({ "url": "https://coderprog.com" }))
+            .to_request();
+        assert_eq!(actix_test::call_service(&app, req).await.status(), 400);
+    }
+
+    #[actix_rt::test]
+    async fn test_batch_reads_ndjson_into_sqlite() {
+        let store: Arc<dyn LinkStore> = Arc::new(SqliteStore::open_in_memory().unwrap());
+        let app = actix_test::init_service(App::new().configure(service(store.clone(), Settings::default()))).await;
+
+        let body = "\"https://a.example\"\n\n{\"url\": \"https://b.example\", \"alias\": \"bee\"}\nnot json\n\"https://a.example\"\n";
+        let req = actix_test::TestRequest::post()
+            .uri("/api/v1/links/batch")
+            .insert_header((header::CONTENT_TYPE, "application/x-ndjson"))
+            .set_payload(body)
+            .to_request();
+        let batch: BatchResponse = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!((batch.succeeded, batch.failed), (3, 1));
+        assert_eq!(batch.results[2].error.as_ref().unwrap().code, "invalid_body");
+        assert_eq!(batch.results[3].link.as_ref().unwrap().creations, 2);
+        assert_eq!(store.resolve("bee").unwrap().unwrap().url, "https://b.example");
+    }
+
+    #[actix_rt::test]
+    async fn test_bulk_resolve_reports_status_without_counting() {
+        let store: Arc<dyn LinkStore> = Arc::new(MemoryStore::new());
+        store.create(Link::new("live", "https://a.example")).unwrap();
+        store.create(Link { expires_at: Some(1), ..Link::new("old", "https://b.example") }).unwrap();
+        store.create(Link::new("gone", "https://c.example")).unwrap();
+        store.delete("gone").unwrap();
+        let app = actix_test::init_service(App::new().configure(service(store.clone(), Settings::default()))).await;
+
+        let req = actix_test::TestRequest::post()
+            .uri("/api/v1/links/resolve")
+            .set_json(json!({ "keys": ["live", "old", "gone", "nope"] }))
+            .to_request();
+        let resolved: ResolveResponse = actix_test::call_and_read_body_json(&app, req).await;
+        let statuses: Vec<_> = resolved.results.iter().map(|r| r.status).collect();
+        assert_eq!(statuses, [LinkStatus::Active, LinkStatus::Expired, LinkStatus::Deleted, LinkStatus::Unknown]);
+        assert_eq!(resolved.results[0].link.as_ref().unwrap().url, "https://a.example");
+        assert_eq!(resolved.results[1].link.as_ref().unwrap().expires_at, Some(1));
+        assert!(resolved.results[2].deleted_at.is_some() && resolved.results[2].link.is_none());
+        assert_eq!(store.resolve("live").unwrap().unwrap().resolutions, 0);
+
+        // Expired links stay inspectable but stop redirecting.
+        let req = actix_test::TestRequest::get().uri("/old").to_request();
+        assert_eq!(actix_test::call_service(&app, req).await.status(), 410);
+        let req = actix_test::TestRequest::get().uri("/api/v1/links/old").to_request();
+        assert_eq!(actix_test::call_service(&app, req).await.status(), 200);
+    }
+
+    #[actix_rt::test]
+    async fn test_stores_the_url_as_sent() {
+        let store: Arc<dyn LinkStore> = Arc::new(MemoryStore::new());
+        let app = actix_test::init_service(App::new().configure(service(store, Settings::default()))).await;
+
+        let req = actix_test::TestRequest::post()
+            .uri("/api/v1/links")
+            .set_json(json!({ "url": " HTTPS://Example.com/Path ", "alias": "as-sent" }))
+            .to_request();
+        let link: LinkResource = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(link.url, "HTTPS://Example.com/Path");
+
+        let req = actix_test::TestRequest::get().uri("/as-sent").to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 307);
+        assert_eq!(resp.headers().get(header::LOCATION).unwrap(), "HTTPS://Example.com/Path");
+
+        for url in ["https://example.com/a\nb", "https://example.com/a b"] {
+            let req = actix_test::TestRequest::post().uri("/api/v1/links").set_json(json!({ "url": url })).to_request();
+            let resp = actix_test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 400);
+            let body: ErrorBody = actix_test::read_body_json(resp).await;
+            assert_eq!(body.code, "invalid_url");
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_rejects_invalid_input() {
+        let store: Arc<dyn LinkStore> = Arc::new(MemoryStore::new());
+        store.create(Link::new("abc", "https://coderprog.com")).unwrap();
+        let app = actix_test::init_service(App::new().configure(service(store, Settings::default()))).await;
+
+        for (method, uri, body, code) in [
+            ("POST", "/api/v1/links", json!({ "url": "ftp://x" }), "unsupported_scheme"),
+            ("POST", "/api/v1/links", json!({ "url": "https://a.example", "tags": ["a b"] }), "invalid_metadata"),
+            ("POST", "/api/v1/links", json!({ "url": "https://a.example", "alias": "api" }), "invalid_alias"),
+            ("PATCH", "/api/v1/links/abc", json!({ "url": "" }), "empty_url"),
+            ("PATCH", "/api/v1/links/abc", json!({ "redirect_code": 200 }), "invalid_body"),
+            ("PATCH", "/api/v1/links/missing", json!({ "tags": [] }), "not_found"),
+        ] {
+            let req = actix_test::TestRequest::default()
+                .method(method.parse().unwrap())
+                .uri(uri)
+                .set_json(body)
+                .to_request();
+            let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+            assert_eq!(body["code"], code, "{} {}", method, uri);
+        }
+    }
+}
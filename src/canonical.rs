@@ -0,0 +1,132 @@
+//! Canonical form of destination URLs, used for hashing and dedupe only.
+//!
+//! Spellings of the same address such as `HTTPS://Example.com`,
+//! `https://example.com/` and `https://example.com:443` share one canonical
+//! form and therefore one key. The string the client sent is still what gets
+//! stored and redirected to.
+
+use url::Url;
+
+/// Query parameters that only track where a click came from.
+const TRACKING_PARAMS: &[&str] = &["fbclid", "gclid", "dclid", "msclkid", "mc_cid", "mc_eid", "_ga"];
+
+fn is_tracking_param(name: &str) -> bool {
+    name.starts_with("utm_") || TRACKING_PARAMS.contains(&name)
+}
+
+/// Lowercases scheme and host, drops the scheme's default port, normalizes
+/// percent-encoding and, if `strip_tracking` is set, removes tracking
+/// parameters such as `utm_source`.
+///
+/// Strings that do not parse as URLs are only trimmed.
+pub fn canonicalize_url(raw: &str, strip_tracking: bool) -> String {
+    let raw = raw.trim();
+    // Parsing already lowercases the scheme and host, removes default ports
+    // and gives hierarchical URLs a `/` path.
+    let mut url = match Url::parse(raw) {
+        Ok(url) => url,
+        Err(_) => return raw.to_string(),
+    };
+
+    let path = normalize_percent_encoding(url.path());
+    url.set_path(&path);
+
+    if let Some(query) = url.query() {
+        let query = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .filter(|pair| {
+                let name = pair.split('=').next().unwrap_or_default();
+                !(strip_tracking && is_tracking_param(name))
+            })
+            .map(normalize_percent_encoding)
+            .collect::<Vec<_>>()
+            .join("&");
+        url.set_query(if query.is_empty() { None } else { Some(&query) });
+    }
+
+    if let Some(fragment) = url.fragment() {
+        let fragment = normalize_percent_encoding(fragment);
+        url.set_fragment(Some(&fragment));
+    }
+
+    let bare_origin = url.path() == "/" && url.query().is_none() && url.fragment().is_none();
+    let mut canonical: String = url.into();
+    if bare_origin {
+        // `https://example.com` is how most people write it and what its key has
+        // always been hashed from, so keep the root slash out of the canonical form.
+        canonical.pop();
+    }
+    canonical
+}
+
+/// Decodes escapes of unreserved characters (`%7E` -> `~`) and uppercases the
+/// hex digits of every other escape (`%2f` -> `%2F`), as RFC 3986 section 6.2.2 advises.
+fn normalize_percent_encoding(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut output = String::with_capacity(input.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && bytes[i + 1].is_ascii_hexdigit()
+            && bytes[i + 2].is_ascii_hexdigit();
+        if escaped {
+            let hex = &input[i + 1..i + 3];
+            let byte = u8::from_str_radix(hex, 16).unwrap();
+            if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+                output.push(byte as char);
+            } else {
+                output.push('%');
+                output.push_str(&hex.to_ascii_uppercase());
+            }
+            i += 3;
+        } else {
+            // Only ASCII escapes are rewritten, so copying char by char is safe.
+            let ch = input[i..].chars().next().unwrap();
+            output.push(ch);
+            i += ch.len_utf8();
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spellings_of_the_same_address_agree() {
+        let canonical = canonicalize_url("https://example.com/", false);
+        assert_eq!(canonical, "https://example.com");
+        assert_eq!(canonicalize_url("HTTPS://Example.com", false), canonical);
+        assert_eq!(canonicalize_url("https://example.com:443", false), canonical);
+        assert_eq!(canonicalize_url(" https://EXAMPLE.com ", false), canonical);
+        assert_ne!(canonicalize_url("http://example.com", false), canonical);
+        assert_ne!(canonicalize_url("https://example.com:8443", false), canonical);
+    }
+
+    #[test]
+    fn normalizes_percent_encoding() {
+        assert_eq!(
+            canonicalize_url("https://example.com/%7euser/a%2fb?q=%41%3d#%61", false),
+            "https://example.com/~user/a%2Fb?q=A%3D#a"
+        );
+    }
+
+    #[test]
+    fn strips_tracking_params_only_when_asked() {
+        let raw = "https://example.com/sale?utm_source=mail&id=7&fbclid=abc";
+        assert_eq!(canonicalize_url(raw, true), "https://example.com/sale?id=7");
+        assert_eq!(canonicalize_url(raw, false), raw);
+        assert_eq!(
+            canonicalize_url("https://example.com/?utm_medium=x", true),
+            "https://example.com"
+        );
+    }
+
+    #[test]
+    fn leaves_unparseable_input_alone() {
+        assert_eq!(canonicalize_url("not a url", true), "not a url");
+    }
+}
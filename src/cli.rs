@@ -0,0 +1,223 @@
+//! Command-line interface. `serve` runs the HTTP server; the other
+//! subcommands work directly on the configured store, with the same key
+//! generation and validation as the API, so data can be inspected and fixed
+//! without going through HTTP.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+use crate::config::{Config, ConfigArgs, StorageBackend};
+use crate::error::ApiError;
+use crate::settings::Settings;
+use crate::store::{Counter, Link, LinkStore, RedirectCode, StoreError};
+use crate::validate::validate_url;
+use crate::window::Window;
+use crate::handlers::{create_alias, get_top_urls, shorten_url};
+
+/// URL shortener server and admin tool
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[command(flatten)]
+    pub config: ConfigArgs,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the HTTP server (the default)
+    Serve,
+    /// Shorten a URL and print its key
+    Shorten {
+        url: String,
+        /// Store under this alias instead of a generated key
+        #[arg(long)]
+        alias: Option<String>,
+        /// Redirect status for this link: 301, 302, 307 or 308
+        #[arg(long = "link-redirect-code", value_name = "CODE")]
+        link_redirect_code: Option<u16>,
+    },
+    /// Print the link stored under a key, without counting the lookup
+    Resolve { key: String },
+    /// Print the top links as `count key url` lines
+    Top {
+        /// Counter to rank by: creations, resolutions or redirects
+        #[arg(long, default_value = "redirects")]
+        by: Counter,
+        /// Time window: 1h, 24h, 7d or all
+        #[arg(long, default_value = "all")]
+        window: Window,
+        #[arg(long, default_value_t = 3)]
+        limit: usize,
+    },
+    /// Write every link as one JSON object per line
+    Export {
+        /// File to write to instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Add links from an `export` file, keeping their keys and counts
+    Import {
+        /// File to read, or `-` for stdin
+        input: PathBuf,
+    },
+}
+
+/// Runs an offline subcommand against `store`, writing its output to `out`.
+/// `Command::Serve` is refused, since it is not one.
+pub fn run(
+    command: Command,
+    config: &Config,
+    settings: &Settings,
+    store: &dyn LinkStore,
+    out: &mut dyn Write,
+) -> Result<(), String> {
+    let changes_store = matches!(command, Command::Shorten { .. } | Command::Import { .. });
+    if changes_store && config.storage.backend == StorageBackend::Memory {
+        return Err("the memory backend does not keep changes; use --storage wal or sqlite".to_string());
+    }
+
+    match command {
+        Command::Serve => Err("serve is not an offline command; run the server instead".to_string()),
+        Command::Shorten { url, alias, link_redirect_code } => {
+            let url = validate_url(&url, settings).map_err(|err| err.to_string())?;
+            let redirect_code = link_redirect_code.map(RedirectCode::try_from).transpose()?;
+            let link = match alias {
+                Some(alias) => create_alias(store, &alias, url, redirect_code),
+                None => shorten_url(store, settings, url, redirect_code).map_err(ApiError::from),
+            }
+            .map_err(|err| err.to_string())?;
+            writeln!(out, "{}", link.key).map_err(|err| err.to_string())
+        }
+        Command::Resolve { key } => {
+            let link = store.resolve(&key).map_err(|err| err.to_string())?;
+            let link = link.ok_or_else(|| ApiError::NotFound(key).to_string())?;
+            let json = serde_json::to_string(&link).map_err(|err| err.to_string())?;
+            writeln!(out, "{}", json).map_err(|err| err.to_string())
+        }
+        Command::Top { by, window, limit } => {
+            for (link, count) in get_top_urls(store, by, window, limit).map_err(|err| err.to_string())? {
+                writeln!(out, "{}\t{}\t{}", count, link.key, link.url).map_err(|err| err.to_string())?;
+            }
+            Ok(())
+        }
+        Command::Export { output } => {
+            let mut links = store.list().map_err(|err| err.to_string())?;
+            links.sort_by(|a, b| a.key.cmp(&b.key));
+            match output {
+                Some(path) => {
+                    let file = File::create(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+                    export(&links, &mut BufWriter::new(file))
+                }
+                None => export(&links, out),
+            }
+            .map_err(|err| err.to_string())
+        }
+        Command::Import { input } => {
+            let (imported, skipped) = if input.as_os_str() == "-" {
+                import(store, io::stdin().lock())?
+            } else {
+                let file = File::open(&input).map_err(|err| format!("{}: {}", input.display(), err))?;
+                import(store, BufReader::new(file))?
+            };
+            writeln!(out, "imported {}, skipped {} existing", imported, skipped).map_err(|err| err.to_string())
+        }
+    }
+}
+
+fn export(links: &[Link], out: &mut dyn Write) -> io::Result<()> {
+    for link in links {
+        serde_json::to_writer(&mut *out, link)?;
+        writeln!(out)?;
+    }
+    out.flush()
+}
+
+/// Creates every link read from `input`, returning how many were added and
+/// how many were skipped because their key already exists.
+fn import(store: &dyn LinkStore, input: impl BufRead) -> Result<(usize, usize), String> {
+    let (mut imported, mut skipped) = (0, 0);
+    for (number, line) in input.lines().enumerate() {
+        let line = line.map_err(|err| err.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let link: Link = serde_json::from_str(&line).map_err(|err| format!("line {}: {}", number + 1, err))?;
+        match store.create(link) {
+            Ok(_) => imported += 1,
+            Err(StoreError::KeyExists(_)) => skipped += 1,
+            Err(err) => return Err(format!("line {}: {}", number + 1, err)),
+        }
+    }
+    Ok((imported, skipped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::SqliteStore;
+
+    fn sqlite_config() -> Config {
+        let mut config = Config::default();
+        config.storage.backend = StorageBackend::Sqlite;
+        config
+    }
+
+    fn run_to_string(command: Command, config: &Config, store: &dyn LinkStore) -> Result<String, String> {
+        let mut out = Vec::new();
+        run(command, config, &Settings::default(), store, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn shorten_uses_the_api_keys() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let config = sqlite_config();
+        let shorten = || Command::Shorten { url: "https://coderprog.com".to_string(), alias: None, link_redirect_code: None };
+
+        assert_eq!(run_to_string(shorten(), &config, &store).unwrap(), "844c01eb2e56\n");
+        run_to_string(shorten(), &config, &store).unwrap();
+        assert_eq!(store.resolve("844c01eb2e56").unwrap().unwrap().creations, 2);
+
+        let resolved = run_to_string(Command::Resolve { key: "844c01eb2e56".to_string() }, &config, &store).unwrap();
+        let link: Link = serde_json::from_str(&resolved).unwrap();
+        assert_eq!(link.url, "https://coderprog.com");
+        assert_eq!(link.resolutions, 0);
+
+        let invalid = Command::Shorten { url: "ftp://x".to_string(), alias: None, link_redirect_code: None };
+        assert!(run_to_string(invalid, &config, &store).is_err());
+        assert!(run_to_string(Command::Resolve { key: "missing".to_string() }, &config, &store).is_err());
+        assert!(run_to_string(Command::Serve, &config, &store).is_err());
+    }
+
+    #[test]
+    fn memory_backend_refuses_changes() {
+        let store = crate::store::MemoryStore::new();
+        let shorten = Command::Shorten { url: "https://coderprog.com".to_string(), alias: None, link_redirect_code: None };
+        assert!(run_to_string(shorten, &Config::default(), &store).is_err());
+    }
+
+    #[test]
+    fn export_then_import() {
+        let source = SqliteStore::open_in_memory().unwrap();
+        source.create(Link { redirects: 5, ..Link::new("aaa", "https://coderprog.com") }).unwrap();
+        source.create(Link::new("bbb", "https://other.example")).unwrap();
+        let config = sqlite_config();
+        let exported = run_to_string(Command::Export { output: None }, &config, &source).unwrap();
+        assert_eq!(exported.lines().count(), 2);
+
+        let target = SqliteStore::open_in_memory().unwrap();
+        target.create(Link::new("bbb", "https://already.example")).unwrap();
+        assert_eq!(import(&target, exported.as_bytes()).unwrap(), (1, 1));
+        assert_eq!(target.resolve("aaa").unwrap().unwrap().redirects, 5);
+        assert_eq!(target.resolve("bbb").unwrap().unwrap().url, "https://already.example");
+
+        let top = run_to_string(Command::Top { by: Counter::Redirects, window: Window::All, limit: 1 }, &config, &target).unwrap();
+        assert_eq!(top, "5\taaa\thttps://coderprog.com\n");
+        assert!(import(&target, "not json\n".as_bytes()).unwrap_err().starts_with("line 1"));
+    }
+}
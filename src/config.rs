@@ -0,0 +1,483 @@
+//! Server configuration, layered from lowest to highest precedence:
+//! built-in defaults, a TOML file, `URLSHORTNER_*` environment variables and
+//! command-line flags.
+//!
+//! ```toml
+//! [server]
+//! bind = "0.0.0.0:8080"
+//! workers = 4
+//!
+//! [storage]
+//! backend = "sqlite"        # memory, wal or sqlite
+//! database = "/data/urlshortner.db"
+//!
+//! [keys]
+//! encoding = "base62"
+//! length = 7
+//!
+//! [links]
+//! redirect_code = 301
+//!
+//! [routes]
+//! redirect_prefix = "/redirect"
+//! landing_url = "https://example.com"
+//! ```
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::keygen::{KeyEncoding, KeyFormat};
+use crate::logging::LogFormat;
+use crate::settings::Settings;
+use crate::store::RedirectCode;
+
+/// Read when neither `--config` nor `URLSHORTNER_CONFIG` names a file.
+const DEFAULT_CONFIG_FILE: &str = "urlshortner.toml";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StorageBackend {
+    #[default]
+    Memory,
+    Wal,
+    Sqlite,
+}
+
+impl FromStr for StorageBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<StorageBackend, String> {
+        match s {
+            "memory" => Ok(StorageBackend::Memory),
+            "wal" => Ok(StorageBackend::Wal),
+            "sqlite" => Ok(StorageBackend::Sqlite),
+            other => Err(format!("unknown storage backend {}, expected memory, wal or sqlite", other)),
+        }
+    }
+}
+
+impl fmt::Display for StorageBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            StorageBackend::Memory => "memory",
+            StorageBackend::Wal => "wal",
+            StorageBackend::Sqlite => "sqlite",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub storage: StorageConfig,
+    pub keys: KeysConfig,
+    pub links: LinksConfig,
+    pub routes: RoutesConfig,
+    pub log: LogConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Address to listen on. Use `0.0.0.0:8080` inside a container.
+    pub bind: String,
+    /// HTTP worker threads; one per CPU core when unset.
+    pub workers: Option<usize>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig { bind: "127.0.0.1:8080".to_string(), workers: None }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    #[serde(deserialize_with = "from_str")]
+    pub backend: StorageBackend,
+    /// SQLite database file.
+    pub database: PathBuf,
+    /// Directory holding the write-ahead log and its snapshot.
+    pub wal_dir: PathBuf,
+    /// Log entries between WAL snapshots.
+    pub snapshot_every: usize,
+    /// Ranking saved by `/top-urls` and reloaded by the memory backend.
+    pub top_urls_path: PathBuf,
+}
+
+impl Default for StorageConfig {
+    fn default() -> StorageConfig {
+        StorageConfig {
+            backend: StorageBackend::default(),
+            database: PathBuf::from("urlshortner.db"),
+            wal_dir: PathBuf::from("data"),
+            snapshot_every: 1000,
+            top_urls_path: PathBuf::from("top_urls.txt"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeysConfig {
+    #[serde(deserialize_with = "from_str")]
+    pub encoding: KeyEncoding,
+    /// Starting key length. Hex keeps its historical 12, the compact encodings default to 7.
+    pub length: Option<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinksConfig {
+    pub redirect_code: RedirectCode,
+    pub strip_tracking: bool,
+    pub allowed_schemes: Vec<String>,
+    pub max_url_length: usize,
+    /// Salt for client address hashes; random per run when unset.
+    pub ip_salt: Option<String>,
+}
+
+impl Default for LinksConfig {
+    fn default() -> LinksConfig {
+        let settings = Settings::default();
+        LinksConfig {
+            redirect_code: settings.redirect_code,
+            strip_tracking: settings.strip_tracking_params,
+            allowed_schemes: settings.allowed_schemes,
+            max_url_length: settings.max_url_length,
+            ip_salt: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoutesConfig {
+    /// Serve short keys under this path instead of the root, e.g. `/redirect`.
+    pub redirect_prefix: String,
+    /// Redirect `GET /` here.
+    pub landing_url: Option<String>,
+    /// Serve `/metrics/domains`.
+    pub domain_metrics: bool,
+}
+
+impl Default for RoutesConfig {
+    fn default() -> RoutesConfig {
+        RoutesConfig { redirect_prefix: String::new(), landing_url: None, domain_metrics: true }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// Filter in `RUST_LOG` syntax, `info` when unset.
+    pub filter: Option<String>,
+    #[serde(deserialize_with = "from_str")]
+    pub format: LogFormat,
+}
+
+/// Command-line overrides; each takes precedence over the file and environment.
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct ConfigArgs {
+    /// TOML configuration file [default: urlshortner.toml if present]
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Address to listen on, e.g. 0.0.0.0:8080
+    #[arg(long, global = true, value_name = "ADDR")]
+    pub bind: Option<String>,
+    /// Number of HTTP worker threads
+    #[arg(long, global = true)]
+    pub workers: Option<usize>,
+    /// Storage backend: memory, wal or sqlite
+    #[arg(long, global = true, value_name = "BACKEND")]
+    pub storage: Option<StorageBackend>,
+    /// SQLite database file
+    #[arg(long, global = true, value_name = "PATH")]
+    pub database: Option<PathBuf>,
+    /// Directory for the write-ahead log
+    #[arg(long, global = true, value_name = "DIR")]
+    pub wal_dir: Option<PathBuf>,
+    /// File the top URLs ranking is saved to
+    #[arg(long, global = true, value_name = "PATH")]
+    pub top_urls_path: Option<PathBuf>,
+    /// Key encoding: hex, base62 or base58
+    #[arg(long, global = true, value_name = "ENCODING")]
+    pub key_encoding: Option<KeyEncoding>,
+    /// Starting length of generated keys
+    #[arg(long, global = true, value_name = "N")]
+    pub key_length: Option<usize>,
+    /// Default redirect status: 301, 302, 307 or 308
+    #[arg(long, global = true, value_name = "CODE", value_parser = parse_redirect_code)]
+    pub redirect_code: Option<RedirectCode>,
+    /// Serve short keys under this path, e.g. /redirect
+    #[arg(long, global = true, value_name = "PATH")]
+    pub redirect_prefix: Option<String>,
+    /// Redirect GET / to this URL
+    #[arg(long, global = true, value_name = "URL")]
+    pub landing_url: Option<String>,
+}
+
+impl Config {
+    /// Builds the configuration from the file, the process environment and `args`.
+    pub fn load(args: &ConfigArgs) -> Result<Config, String> {
+        Config::load_with(args, |name| std::env::var(name).ok())
+    }
+
+    fn load_with(args: &ConfigArgs, env: impl Fn(&str) -> Option<String>) -> Result<Config, String> {
+        let path = args.config.clone().or_else(|| env("URLSHORTNER_CONFIG").map(PathBuf::from));
+        let mut config = match path {
+            Some(path) => Config::from_file(&path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => Config::from_file(Path::new(DEFAULT_CONFIG_FILE))?,
+            None => Config::default(),
+        };
+        config.apply_env(env)?;
+        config.apply_args(args);
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        toml::from_str(&text).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    fn apply_env(&mut self, env: impl Fn(&str) -> Option<String>) -> Result<(), String> {
+        fn parse<T: FromStr>(name: &str, value: String) -> Result<T, String>
+        where
+            T::Err: fmt::Display,
+        {
+            value.parse().map_err(|err| format!("{}: {}", name, err))
+        }
+
+        if let Some(bind) = env("URLSHORTNER_BIND") {
+            self.server.bind = bind;
+        }
+        if let Some(workers) = env("URLSHORTNER_WORKERS") {
+            self.server.workers = Some(parse("URLSHORTNER_WORKERS", workers)?);
+        }
+        if let Some(backend) = env("URLSHORTNER_STORAGE") {
+            self.storage.backend = parse("URLSHORTNER_STORAGE", backend)?;
+        }
+        if let Some(database) = env("URLSHORTNER_DATABASE") {
+            self.storage.database = PathBuf::from(database);
+        }
+        if let Some(dir) = env("URLSHORTNER_WAL_DIR") {
+            self.storage.wal_dir = PathBuf::from(dir);
+        }
+        if let Some(path) = env("URLSHORTNER_TOP_URLS_PATH") {
+            self.storage.top_urls_path = PathBuf::from(path);
+        }
+        if let Some(encoding) = env("URLSHORTNER_KEY_ENCODING") {
+            self.keys.encoding = parse("URLSHORTNER_KEY_ENCODING", encoding)?;
+        }
+        if let Some(length) = env("URLSHORTNER_KEY_LENGTH") {
+            self.keys.length = Some(parse("URLSHORTNER_KEY_LENGTH", length)?);
+        }
+        if let Some(code) = env("URLSHORTNER_REDIRECT_CODE") {
+            self.links.redirect_code = parse_redirect_code(&code).map_err(|err| format!("URLSHORTNER_REDIRECT_CODE: {}", err))?;
+        }
+        if let Some(strip) = env("URLSHORTNER_STRIP_TRACKING") {
+            self.links.strip_tracking = parse("URLSHORTNER_STRIP_TRACKING", strip)?;
+        }
+        if let Some(schemes) = env("URLSHORTNER_ALLOWED_SCHEMES") {
+            self.links.allowed_schemes = schemes
+                .split(',')
+                .map(|scheme| scheme.trim().to_ascii_lowercase())
+                .filter(|scheme| !scheme.is_empty())
+                .collect();
+        }
+        if let Some(max) = env("URLSHORTNER_MAX_URL_LENGTH") {
+            self.links.max_url_length = parse("URLSHORTNER_MAX_URL_LENGTH", max)?;
+        }
+        if let Some(salt) = env("URLSHORTNER_IP_SALT") {
+            self.links.ip_salt = Some(salt);
+        }
+        if let Some(prefix) = env("URLSHORTNER_REDIRECT_PREFIX") {
+            self.routes.redirect_prefix = prefix;
+        }
+        if let Some(url) = env("URLSHORTNER_LANDING_URL") {
+            self.routes.landing_url = Some(url);
+        }
+        if let Some(enabled) = env("URLSHORTNER_DOMAIN_METRICS") {
+            self.routes.domain_metrics = parse("URLSHORTNER_DOMAIN_METRICS", enabled)?;
+        }
+        if let Some(filter) = env("URLSHORTNER_LOG") {
+            self.log.filter = Some(filter);
+        }
+        if let Some(format) = env("URLSHORTNER_LOG_FORMAT") {
+            self.log.format = parse("URLSHORTNER_LOG_FORMAT", format)?;
+        }
+        Ok(())
+    }
+
+    fn apply_args(&mut self, args: &ConfigArgs) {
+        if let Some(bind) = &args.bind {
+            self.server.bind = bind.clone();
+        }
+        if args.workers.is_some() {
+            self.server.workers = args.workers;
+        }
+        if let Some(backend) = args.storage {
+            self.storage.backend = backend;
+        }
+        if let Some(database) = &args.database {
+            self.storage.database = database.clone();
+        }
+        if let Some(dir) = &args.wal_dir {
+            self.storage.wal_dir = dir.clone();
+        }
+        if let Some(path) = &args.top_urls_path {
+            self.storage.top_urls_path = path.clone();
+        }
+        if let Some(encoding) = args.key_encoding {
+            self.keys.encoding = encoding;
+        }
+        if args.key_length.is_some() {
+            self.keys.length = args.key_length;
+        }
+        if let Some(code) = args.redirect_code {
+            self.links.redirect_code = code;
+        }
+        if let Some(prefix) = &args.redirect_prefix {
+            self.routes.redirect_prefix = prefix.clone();
+        }
+        if let Some(url) = &args.landing_url {
+            self.routes.landing_url = Some(url.clone());
+        }
+    }
+
+    /// The handler settings this configuration describes.
+    pub fn settings(&self) -> Result<Settings, String> {
+        let length = match self.keys.length {
+            Some(length) => length,
+            None if self.keys.encoding == KeyEncoding::Hex => KeyFormat::default().length,
+            None => 7,
+        };
+        let prefix = self.routes.redirect_prefix.trim_end_matches('/');
+        if !prefix.is_empty() && !prefix.starts_with('/') {
+            return Err(format!("redirect prefix {:?} must start with /", prefix));
+        }
+        let mut settings = Settings {
+            redirect_code: self.links.redirect_code,
+            key_format: KeyFormat::new(self.keys.encoding, length)?,
+            strip_tracking_params: self.links.strip_tracking,
+            allowed_schemes: self.links.allowed_schemes.clone(),
+            max_url_length: self.links.max_url_length,
+            top_urls_path: self.storage.top_urls_path.clone(),
+            redirect_prefix: prefix.to_string(),
+            landing_url: self.routes.landing_url.clone(),
+            domain_metrics: self.routes.domain_metrics,
+            ..Settings::default()
+        };
+        if let Some(salt) = &self.links.ip_salt {
+            settings.ip_hash_salt = salt.clone();
+        }
+        Ok(settings)
+    }
+}
+
+fn parse_redirect_code(code: &str) -> Result<RedirectCode, String> {
+    code.parse::<u16>().map_err(|err| err.to_string()).and_then(RedirectCode::try_from)
+}
+
+/// Deserializes a value through its `FromStr` impl.
+fn from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    #[test]
+    fn defaults_match_settings() {
+        let config = Config::load_with(&ConfigArgs::default(), no_env).unwrap();
+        assert_eq!(config.server.bind, "127.0.0.1:8080");
+        assert_eq!(config.storage.backend, StorageBackend::Memory);
+        let settings = config.settings().unwrap();
+        assert_eq!(settings.key_format, KeyFormat::default());
+        assert_eq!(settings.redirect_code, RedirectCode::TemporaryRedirect);
+    }
+
+    #[test]
+    fn file_then_env_then_flags() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("urlshortner.toml");
+        std::fs::write(
+            &path,
+            r#"
+            [server]
+            bind = "0.0.0.0:9000"
+            workers = 2
+
+            [storage]
+            backend = "sqlite"
+            database = "/srv/links.db"
+
+            [keys]
+            encoding = "base62"
+
+            [links]
+            redirect_code = 301
+            "#,
+        )
+        .unwrap();
+
+        let env: HashMap<&str, &str> = [("URLSHORTNER_WORKERS", "8"), ("URLSHORTNER_KEY_LENGTH", "9")].into();
+        let env = |name: &str| env.get(name).map(|value| value.to_string());
+        let args = ConfigArgs {
+            config: Some(path),
+            key_length: Some(10),
+            redirect_code: Some(RedirectCode::PermanentRedirect),
+            ..ConfigArgs::default()
+        };
+        let config = Config::load_with(&args, env).unwrap();
+
+        assert_eq!(config.server.bind, "0.0.0.0:9000");
+        assert_eq!(config.server.workers, Some(8));
+        assert_eq!(config.storage.backend, StorageBackend::Sqlite);
+        assert_eq!(config.storage.database, PathBuf::from("/srv/links.db"));
+        let settings = config.settings().unwrap();
+        assert_eq!(settings.key_format, KeyFormat::new(KeyEncoding::Base62, 10).unwrap());
+        assert_eq!(settings.redirect_code, RedirectCode::PermanentRedirect);
+    }
+
+    #[test]
+    fn compact_encodings_default_to_seven() {
+        let env = |name: &str| (name == "URLSHORTNER_KEY_ENCODING").then(|| "base58".to_string());
+        let config = Config::load_with(&ConfigArgs::default(), env).unwrap();
+        assert_eq!(config.settings().unwrap().key_format.length, 7);
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        let env = |name: &str| (name == "URLSHORTNER_STORAGE").then(|| "redis".to_string());
+        assert!(Config::load_with(&ConfigArgs::default(), env).unwrap_err().contains("URLSHORTNER_STORAGE"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bad.toml");
+        std::fs::write(&path, "[server]\nport = 80\n").unwrap();
+        let args = ConfigArgs { config: Some(path), ..ConfigArgs::default() };
+        assert!(Config::load_with(&args, no_env).is_err());
+
+        let args = ConfigArgs { key_length: Some(0), ..ConfigArgs::default() };
+        assert!(Config::load_with(&args, no_env).unwrap().settings().is_err());
+
+        let args = ConfigArgs { redirect_prefix: Some("redirect".to_string()), ..ConfigArgs::default() };
+        assert!(Config::load_with(&args, no_env).unwrap().settings().is_err());
+    }
+}
@@ -0,0 +1,90 @@
+//! Aggregation of link counts by destination domain.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use url::{Host, Url};
+
+use crate::store::Link;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DomainCount {
+    pub domain: String,
+    pub count: u64,
+}
+
+/// The host a destination URL points at: lowercased, without port or
+/// credentials, and with internationalized names in their ASCII (punycode)
+/// form so both spellings count as one domain. Destinations stored without a
+/// scheme (`example.com/page`) are read as `http://` URLs.
+pub fn domain_of(url: &str) -> Option<String> {
+    let url = url.trim();
+    let parsed = match Url::parse(url) {
+        Ok(parsed) if parsed.has_host() => parsed,
+        // `example.com:8080/x` parses with `example.com` as its scheme.
+        Ok(parsed) if !parsed.scheme().contains('.') => return None,
+        _ => Url::parse(&format!("http://{}", url)).ok()?,
+    };
+    match parsed.host()? {
+        Host::Domain(domain) => Some(domain.trim_end_matches('.').to_string()),
+        Host::Ipv4(addr) => Some(addr.to_string()),
+        Host::Ipv6(addr) => Some(addr.to_string()),
+    }
+}
+
+/// Sums the counts of `links` per domain, highest first, keeping `limit` domains.
+/// Links whose destination has no recognizable host are skipped.
+pub fn domain_counts(links: &[(Link, u64)], limit: usize) -> Vec<DomainCount> {
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    for (link, count) in links {
+        if let Some(domain) = domain_of(&link.url) {
+            *counts.entry(domain).or_insert(0) += count;
+        }
+    }
+
+    let mut counts: Vec<DomainCount> = counts
+        .into_iter()
+        .map(|(domain, count)| DomainCount { domain, count })
+        .collect();
+    counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.domain.cmp(&b.domain)));
+    counts.truncate(limit);
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_hosts() {
+        assert_eq!(domain_of("https://coderprog.com").as_deref(), Some("coderprog.com"));
+        assert_eq!(domain_of("https://Coderprog.COM:8443/a?b").as_deref(), Some("coderprog.com"));
+        assert_eq!(domain_of("https://user:pw@coderprog.com/").as_deref(), Some("coderprog.com"));
+        assert_eq!(domain_of("coderprog.com").as_deref(), Some("coderprog.com"));
+        assert_eq!(domain_of("coderprog.com:8080/path").as_deref(), Some("coderprog.com"));
+        assert_eq!(domain_of("http://[::1]:8080/").as_deref(), Some("::1"));
+        assert_eq!(domain_of("http://127.0.0.1/").as_deref(), Some("127.0.0.1"));
+        assert_eq!(domain_of("mailto:someone@coderprog.com"), None);
+        assert_eq!(domain_of(""), None);
+    }
+
+    #[test]
+    fn internationalized_names_count_once() {
+        assert_eq!(domain_of("https://bücher.example/").as_deref(), Some("xn--bcher-kva.example"));
+        assert_eq!(domain_of("https://xn--bcher-kva.example/x").as_deref(), Some("xn--bcher-kva.example"));
+    }
+
+    #[test]
+    fn sums_per_domain() {
+        let links = vec![
+            (Link::new("a", "https://coderprog.com"), 5),
+            (Link::new("b", "https://coderprog.com:443/x"), 3),
+            (Link::new("c", "https://other.example/"), 4),
+            (Link::new("d", "mailto:"), 9),
+        ];
+        let counts = domain_counts(&links, 10);
+        let counts: Vec<(&str, u64)> = counts.iter().map(|c| (c.domain.as_str(), c.count)).collect();
+        assert_eq!(counts, [("coderprog.com", 8), ("other.example", 4)]);
+        assert_eq!(domain_counts(&links, 1).len(), 1);
+    }
+}
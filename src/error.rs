@@ -0,0 +1,126 @@
+//! Errors returned to API clients as `{"code": ..., "message": ...}` JSON bodies.
+
+use std::fmt;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use serde::{Deserialize, Serialize};
+
+use crate::store::StoreError;
+
+#[derive(Debug)]
+pub enum ApiError {
+    /// The request body could not be read as the expected JSON.
+    InvalidBody(String),
+    /// The query string could not be parsed.
+    InvalidQuery(String),
+    /// No destination URL was given.
+    EmptyUrl,
+    /// The destination is longer than the configured limit.
+    UrlTooLong(usize),
+    /// The destination does not parse as an absolute URL.
+    InvalidUrl(String),
+    /// The destination's scheme is not in the allowed list.
+    UnsupportedScheme(String),
+    /// The requested alias breaks the alias rules.
+    InvalidAlias(String),
+    /// The requested alias is already in use.
+    AliasTaken(String),
+    /// The URL is already shortened under this key, with other settings than requested.
+    LinkExists(String),
+    /// Tags or owner break the metadata rules.
+    InvalidMetadata(String),
+    /// No link is stored under the key.
+    NotFound(String),
+    /// The link under the key is past its expiry.
+    Expired(String),
+    Store(StoreError),
+}
+
+/// JSON body of an error response, also used for per-item errors in batches.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ErrorBody {
+    pub code: String,
+    pub message: String,
+}
+
+impl From<&ApiError> for ErrorBody {
+    fn from(err: &ApiError) -> ErrorBody {
+        ErrorBody {
+            code: err.code().to_string(),
+            message: err.to_string(),
+        }
+    }
+}
+
+impl ApiError {
+    /// Machine-readable identifier clients can match on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::InvalidBody(_) => "invalid_body",
+            ApiError::InvalidQuery(_) => "invalid_query",
+            ApiError::EmptyUrl => "empty_url",
+            ApiError::UrlTooLong(_) => "url_too_long",
+            ApiError::InvalidUrl(_) => "invalid_url",
+            ApiError::UnsupportedScheme(_) => "unsupported_scheme",
+            ApiError::InvalidAlias(_) => "invalid_alias",
+            ApiError::AliasTaken(_) => "alias_taken",
+            ApiError::LinkExists(_) => "link_exists",
+            ApiError::InvalidMetadata(_) => "invalid_metadata",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Expired(_) => "expired",
+            ApiError::Store(_) => "storage_error",
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::InvalidBody(reason) => write!(f, "invalid request body: {}", reason),
+            ApiError::InvalidQuery(reason) => write!(f, "invalid query string: {}", reason),
+            ApiError::EmptyUrl => f.write_str("url must not be empty"),
+            ApiError::UrlTooLong(max) => write!(f, "url is longer than {} bytes", max),
+            ApiError::InvalidUrl(reason) => write!(f, "invalid url: {}", reason),
+            ApiError::UnsupportedScheme(scheme) => write!(f, "url scheme {} is not allowed", scheme),
+            ApiError::InvalidAlias(reason) => write!(f, "invalid alias: {}", reason),
+            ApiError::AliasTaken(alias) => write!(f, "alias {} is already taken", alias),
+            ApiError::LinkExists(key) => write!(f, "url is already shortened as {} with other settings", key),
+            ApiError::InvalidMetadata(reason) => write!(f, "invalid metadata: {}", reason),
+            ApiError::NotFound(key) => write!(f, "no link for key {}", key),
+            ApiError::Expired(key) => write!(f, "link {} has expired", key),
+            ApiError::Store(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<StoreError> for ApiError {
+    fn from(err: StoreError) -> ApiError {
+        ApiError::Store(err)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::InvalidBody(_)
+            | ApiError::InvalidQuery(_)
+            | ApiError::EmptyUrl
+            | ApiError::UrlTooLong(_)
+            | ApiError::InvalidUrl(_)
+            | ApiError::UnsupportedScheme(_)
+            | ApiError::InvalidAlias(_)
+            | ApiError::InvalidMetadata(_) => StatusCode::BAD_REQUEST,
+            ApiError::AliasTaken(_) | ApiError::LinkExists(_) => StatusCode::CONFLICT,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Expired(_) => StatusCode::GONE,
+            ApiError::Store(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(ErrorBody::from(self))
+    }
+}
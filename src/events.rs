@@ -0,0 +1,92 @@
+//! Turns incoming resolve and redirect requests into click events.
+
+use actix_web::http::header;
+use actix_web::HttpRequest;
+use tiny_keccak::{Hasher, Sha3};
+
+use crate::settings::Settings;
+use crate::store::{unix_now, ClickEvent, ClickKind};
+
+/// Header values longer than this are cut before being stored.
+const MAX_HEADER_CHARS: usize = 512;
+
+/// Describes `req` as a click on `key`.
+pub fn click_event(req: &HttpRequest, key: &str, kind: ClickKind, settings: &Settings) -> ClickEvent {
+    let header_value = |name: header::HeaderName| {
+        req.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(MAX_HEADER_CHARS).collect::<String>())
+    };
+
+    let client_ip_hash = req
+        .connection_info()
+        .realip_remote_addr()
+        .map(|addr| hash_client_ip(strip_port(addr), &settings.ip_hash_salt));
+
+    ClickEvent {
+        key: key.to_string(),
+        at: unix_now(),
+        kind,
+        referrer: header_value(header::REFERER),
+        user_agent: header_value(header::USER_AGENT),
+        client_ip_hash,
+    }
+}
+
+/// Salted SHA3 of the address, shortened to 16 hex characters: enough to tell
+/// clients apart, not enough to be worth reversing.
+pub fn hash_client_ip(ip: &str, salt: &str) -> String {
+    let mut hasher = Sha3::v256();
+    hasher.update(salt.as_bytes());
+    hasher.update(ip.as_bytes());
+    let mut result = [0u8; 32];
+    hasher.finalize(&mut result);
+    result.iter().take(8).map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// `1.2.3.4:5678` -> `1.2.3.4`, `[::1]:80` -> `::1`; bare addresses pass through.
+fn strip_port(addr: &str) -> &str {
+    match addr.parse::<std::net::SocketAddr>() {
+        Ok(_) => {
+            let host = addr.rsplit_once(':').map_or(addr, |(host, _)| host);
+            host.trim_start_matches('[').trim_end_matches(']')
+        }
+        Err(_) => addr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn captures_headers_and_hashes_address() {
+        let settings = Settings {
+            ip_hash_salt: "pepper".to_string(),
+            ..Settings::default()
+        };
+        let req = TestRequest::get()
+            .insert_header((header::REFERER, "https://news.example/post"))
+            .insert_header((header::USER_AGENT, "curl/8.0"))
+            .peer_addr("203.0.113.9:50000".parse().unwrap())
+            .to_http_request();
+
+        let event = click_event(&req, "abc", ClickKind::Redirect, &settings);
+        assert_eq!(event.key, "abc");
+        assert_eq!(event.referrer.as_deref(), Some("https://news.example/post"));
+        assert_eq!(event.user_agent.as_deref(), Some("curl/8.0"));
+        let hash = event.client_ip_hash.unwrap();
+        assert_eq!(hash, hash_client_ip("203.0.113.9", "pepper"));
+        assert!(!hash.contains("203.0.113.9"));
+    }
+
+    #[test]
+    fn strips_ports() {
+        assert_eq!(strip_port("203.0.113.9:50000"), "203.0.113.9");
+        assert_eq!(strip_port("[2001:db8::1]:443"), "2001:db8::1");
+        assert_eq!(strip_port("2001:db8::1"), "2001:db8::1");
+        assert_eq!(strip_port("203.0.113.9"), "203.0.113.9");
+    }
+}
@@ -0,0 +1,851 @@
+//! The HTTP handlers and the shortening logic they share with the command line.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info, instrument, trace, warn};
+
+use crate::alias::validate_alias;
+use crate::canonical::canonicalize_url;
+use crate::domains::domain_counts;
+use crate::error::ApiError;
+use crate::events::click_event;
+use crate::keygen::{generate_shortened_url_key, KeyFormat};
+use crate::metrics;
+use crate::settings::Settings;
+use crate::store::{unix_now, ClickEvent, ClickKind, Counter, Link, LinkStore, RedirectCode, StoreError};
+use crate::validate::validate_url;
+use crate::window::Window;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UrlData {
+    pub url: String,
+    /// Readable key to use instead of a generated one, e.g. `spring-sale`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    /// Redirect status for a newly created link; the server default applies when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redirect_code: Option<RedirectCode>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ResponseData {
+    pub original_url_received: String,
+    pub shortened_url: String,
+    pub original_url_retrieved: String,
+    pub original_url_matches: bool,
+    pub creation_count: u64,
+    pub resolution_count: u64,
+    pub redirect_count: u64,
+    pub redirect_code: RedirectCode,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TopUrlData {
+    pub url: String,
+    pub shortened_url: String,
+    pub creation_count: u64,
+    pub resolution_count: u64,
+    pub redirect_count: u64,
+    /// The count the list is ranked by: the `by` counter within `window`.
+    pub window_count: u64,
+}
+
+impl From<(Link, u64)> for TopUrlData {
+    fn from((link, window_count): (Link, u64)) -> TopUrlData {
+        TopUrlData {
+            url: link.url,
+            shortened_url: link.key,
+            creation_count: link.creations,
+            resolution_count: link.resolutions,
+            redirect_count: link.redirects,
+            window_count,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ResolveQuery {
+    /// Look the key up without counting the lookup.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TopUrlsQuery {
+    /// Counter to rank by.
+    #[serde(default = "default_ranking")]
+    by: Counter,
+    #[serde(default)]
+    window: Window,
+    #[serde(default = "default_top_limit")]
+    limit: usize,
+}
+
+fn default_top_limit() -> usize {
+    3
+}
+
+/// Upper bound on `limit` for `/top-urls`.
+const MAX_TOP_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct DomainMetricsQuery {
+    /// Counter to sum per domain.
+    #[serde(default = "default_ranking")]
+    by: Counter,
+    #[serde(default)]
+    window: Window,
+    #[serde(default = "default_domains_limit")]
+    limit: usize,
+}
+
+fn default_domains_limit() -> usize {
+    10
+}
+
+fn default_ranking() -> Counter {
+    Counter::Redirects
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct EventsQuery {
+    /// Only events at or after this Unix time.
+    #[serde(default)]
+    since: u64,
+    #[serde(default = "default_events_limit")]
+    limit: usize,
+}
+
+fn default_events_limit() -> usize {
+    100
+}
+
+/// Upper bound on `limit` for `/events/{short_key}`.
+const MAX_EVENTS_LIMIT: usize = 1000;
+
+/// Writes one `url:creations:resolutions:redirects` line per link.
+pub fn save_top_urls(path: &Path, urls: &[Link]) -> std::io::Result<()> {
+    let file = OpenOptions::new()
+        .write(true)
+        .truncate(true)  // Truncate the file before writing
+        .create(true)
+        .open(path)?;
+    let mut writer = BufWriter::new(file);
+
+    for link in urls {
+        writeln!(writer, "{}:{}:{}:{}", link.url, link.creations, link.resolutions, link.redirects)?;
+    }
+
+    Ok(())
+}
+
+/// Stores `original_url` under the shortest digest prefix that is either free or
+/// already points at the same URL, bumping the count of an existing link.
+pub fn shorten_url(
+    store: &dyn LinkStore,
+    settings: &Settings,
+    original_url: &str,
+    redirect_code: Option<RedirectCode>,
+) -> Result<Link, StoreError> {
+    let (link, created) = shorten_link(store, settings, Link { redirect_code, ..Link::new("", original_url) })?;
+    if created {
+        return Ok(link);
+    }
+    let creations = store.increment(&link.key, Counter::Creations)?.unwrap_or(link.creations);
+    debug!(key = %link.key, creations, "URL already shortened");
+    Ok(Link { creations, ..link })
+}
+
+/// Stores `link` under the shortest digest prefix of its URL that is either
+/// free or already points at the same URL, ignoring `link.key`. Returns the
+/// stored link and whether it was created; an existing link is returned as
+/// stored, and counting the resubmission is left to the caller.
+///
+/// Keys are derived from the canonical form of the URL, and an existing link
+/// counts as "the same URL" when its canonical form matches. A prefix taken by a
+/// different URL is a collision: the key is lengthened by one character and
+/// tried again, so two URLs never share or overwrite a key. An expired link for
+/// the same URL is treated the same way, so its key keeps answering "expired"
+/// and the URL gets a fresh, active link one character longer.
+pub fn shorten_link(store: &dyn LinkStore, settings: &Settings, link: Link) -> Result<(Link, bool), StoreError> {
+    let canonical_url = canonicalize_url(&link.url, settings.strip_tracking_params);
+    let KeyFormat { encoding, mut length } = settings.key_format;
+    let now = unix_now();
+    while length <= encoding.max_len() {
+        let key = generate_shortened_url_key(&canonical_url, encoding, length);
+        match store.resolve(&key)? {
+            Some(existing)
+                if !existing.is_expired(now)
+                    && canonicalize_url(&existing.url, settings.strip_tracking_params) == canonical_url =>
+            {
+                return Ok((existing, false));
+            }
+            Some(existing) => {
+                debug!(%key, other_url = %existing.url, "key collides, lengthening");
+                length += 1;
+            }
+            None => match store.create(Link { key: key.clone(), ..link.clone() }) {
+                Ok(link) => {
+                    info!(%key, "link created");
+                    return Ok((link, true));
+                }
+                // Someone stored this key since we looked; check it again.
+                Err(StoreError::KeyExists(_)) => continue,
+                Err(err) => return Err(err),
+            },
+        }
+    }
+    Err(StoreError::KeyExists(generate_shortened_url_key(&canonical_url, encoding, encoding.max_len())))
+}
+
+/// Stores `original_url` under a user-chosen alias, failing if the alias is invalid or taken.
+pub fn create_alias(
+    store: &dyn LinkStore,
+    alias: &str,
+    original_url: &str,
+    redirect_code: Option<RedirectCode>,
+) -> Result<Link, ApiError> {
+    create_alias_link(store, Link { redirect_code, ..Link::new(alias, original_url) })
+}
+
+/// Stores `link` under its key as a user-chosen alias, failing if the alias is invalid or taken.
+pub fn create_alias_link(store: &dyn LinkStore, link: Link) -> Result<Link, ApiError> {
+    validate_alias(&link.key)?;
+    store
+        .create(link)
+        .map_err(|err| match err {
+            StoreError::KeyExists(alias) => ApiError::AliasTaken(alias),
+            err => err.into(),
+        })
+}
+
+#[instrument(skip_all)]
+pub(crate) async fn shorten_and_retrieve_url(
+    store: web::Data<dyn LinkStore>,
+    settings: web::Data<Settings>,
+    req_body: web::Json<UrlData>,
+) -> Result<HttpResponse, ApiError> {
+    let original_url_received = req_body.url.clone();
+    let url = validate_url(&original_url_received, &settings)?;
+
+    /* let shortened_url_key = {
+        let mut hasher = Sha3::v256();
+        hasher.update(original_url_received.as_bytes());
+        let mut result = [0u8; 32];
+        hasher.finalize(&mut result);
+
+        let mut shortened_url = String::new();
+        for byte in result.iter().take(6) {
+            shortened_url.push_str(&format!("{:02x}", byte));
+        }
+        shortened_url
+    };
+ */
+    let link = match &req_body.alias {
+        Some(alias) => create_alias(store.get_ref(), alias, url, req_body.redirect_code)?,
+        None => shorten_url(store.get_ref(), &settings, url, req_body.redirect_code)?,
+    };
+
+    Ok(HttpResponse::Ok().json(ResponseData {
+        original_url_received: original_url_received.clone(),
+        shortened_url: link.key.clone(),
+        original_url_retrieved: link.url.clone(),
+        original_url_matches: link.url == url,
+        creation_count: link.creations,
+        resolution_count: link.resolutions,
+        redirect_count: link.redirects,
+        redirect_code: settings.redirect_code_for(&link),
+    }))
+}
+
+#[instrument(skip_all, fields(key = %req_body.url, dry_run = query.dry_run))]
+pub(crate) async fn retrieve_original_url(
+    req: HttpRequest,
+    store: web::Data<dyn LinkStore>,
+    settings: web::Data<Settings>,
+    query: web::Query<ResolveQuery>,
+    req_body: web::Json<UrlData>,
+) -> Result<HttpResponse, ApiError> {
+    let shortened_url_received = req_body.url.clone();
+
+    // Check if the shortened URL exists in the storage, counting the lookup unless asked not to
+    let mut link = match lookup_link(store.get_ref(), &shortened_url_received)? {
+        Lookup::Active(link) => link,
+        Lookup::Expired(_) => return Err(ApiError::Expired(shortened_url_received)),
+        Lookup::Deleted(_) | Lookup::Unknown => {
+            debug!("key not found");
+            return Err(ApiError::NotFound(shortened_url_received));
+        }
+    };
+    if !query.dry_run {
+        if let Some(request_count) = store.increment(&link.key, Counter::Resolutions)? {
+            debug!(resolutions = request_count, "lookup counted");
+            link.resolutions = request_count;
+        }
+        record_click(store.get_ref(), click_event(&req, &link.key, ClickKind::Resolve, &settings));
+    }
+
+    Ok(HttpResponse::Ok().json(ResponseData {
+        original_url_received: link.url.clone(),
+        shortened_url: shortened_url_received.clone(),
+        original_url_retrieved: link.url.clone(),
+        original_url_matches: true,
+        creation_count: link.creations,
+        resolution_count: link.resolutions,
+        redirect_count: link.redirects,
+        redirect_code: settings.redirect_code_for(&link),
+    }))
+}
+
+/// What a key currently points at.
+#[derive(Debug)]
+pub enum Lookup {
+    Active(Link),
+    /// Still stored, but past its expiry; it no longer redirects.
+    Expired(Link),
+    /// Nothing is stored under the key any more; it was deleted at this time.
+    Deleted(u64),
+    Unknown,
+}
+
+/// Looks a key up without counting the lookup.
+pub fn lookup_link(store: &dyn LinkStore, key: &str) -> Result<Lookup, StoreError> {
+    if let Some(link) = store.resolve(key)? {
+        return Ok(if link.is_expired(unix_now()) { Lookup::Expired(link) } else { Lookup::Active(link) });
+    }
+    Ok(match store.deleted_at(key)? {
+        Some(deleted_at) => Lookup::Deleted(deleted_at),
+        None => Lookup::Unknown,
+    })
+}
+
+/// Saves a click event. A failure is only reported, never passed on to the
+/// client: the lookup itself already succeeded.
+fn record_click(store: &dyn LinkStore, event: ClickEvent) {
+    if let Err(err) = store.record_event(event) {
+        warn!(error = %err, "failed to record click");
+    }
+}
+
+// Handler function for listing the recorded clicks of a short key, newest first
+#[instrument(skip_all, fields(key = %short_key))]
+pub(crate) async fn link_events(
+    store: web::Data<dyn LinkStore>,
+    short_key: web::Path<String>,
+    query: web::Query<EventsQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let short_key = short_key.into_inner();
+    if store.resolve(&short_key)?.is_none() {
+        return Err(ApiError::NotFound(short_key));
+    }
+
+    let events = store.events(&short_key, query.since, query.limit.min(MAX_EVENTS_LIMIT))?;
+    Ok(HttpResponse::Ok().json(events))
+}
+
+// Handler function for redirecting a short key to its original URL
+#[instrument(skip_all, fields(key = %short_key))]
+pub(crate) async fn redirect_to_original(
+    req: HttpRequest,
+    store: web::Data<dyn LinkStore>,
+    settings: web::Data<Settings>,
+    short_key: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let short_key = short_key.into_inner();
+
+    match lookup_link(store.get_ref(), &short_key)? {
+        Lookup::Active(link) => {
+            store.increment(&short_key, Counter::Redirects)?;
+            record_click(store.get_ref(), click_event(&req, &short_key, ClickKind::Redirect, &settings));
+            Ok(HttpResponse::build(settings.redirect_code_for(&link).status())
+                .append_header((header::LOCATION, link.url))
+                .finish())
+        }
+        Lookup::Expired(_) => Err(ApiError::Expired(short_key)),
+        Lookup::Deleted(_) | Lookup::Unknown => Err(ApiError::NotFound(short_key)),
+    }
+}
+
+// Handler function for the landing page on `GET /`
+pub(crate) async fn landing_page(settings: web::Data<Settings>) -> Result<HttpResponse, ApiError> {
+    let landing_url = settings.landing_url.clone().ok_or_else(|| ApiError::NotFound(String::new()))?;
+    Ok(HttpResponse::build(settings.redirect_code.status())
+        .append_header((header::LOCATION, landing_url))
+        .finish())
+}
+
+fn parse_top_url_line(line: &str) -> Option<Link> {
+    // URLs contain colons themselves, so the counts are split off the end.
+    let parts: Vec<&str> = line.rsplitn(4, ':').collect();
+    if let [redirects, resolutions, creations, url] = parts[..] {
+        let counts = (creations.trim().parse(), resolutions.trim().parse(), redirects.trim().parse());
+        if let (Ok(creations), Ok(resolutions), Ok(redirects)) = counts {
+            let url = url.trim();
+            return Some(Link { creations, resolutions, redirects, ..Link::new(url, url) });
+        }
+    }
+
+    let (url, count) = line.rsplit_once(':')?;
+    let url = url.trim();
+    let creations = count.trim().parse().unwrap_or(0);
+    Some(Link { creations, ..Link::new(url, url) })
+}
+
+/// Reads lines written by `save_top_urls`. Older files hold a single
+/// `url:count` per line; that count is loaded as creations.
+pub fn load_top_urls(path: &Path, store: &dyn LinkStore) -> std::io::Result<()> {
+    let file = File::open(path)?;
+    let reader = BufReader::new(file);
+
+    let mut loaded = 0;
+    for line in reader.lines() {
+        let line = line?;
+        let Some(link) = parse_top_url_line(&line) else {
+            continue;
+        };
+        trace!(url = %link.url, link.creations, link.resolutions, link.redirects, "loading saved URL");
+        // Store the URL along with its counts
+        let url = link.url.clone();
+        match store.create(link) {
+            Ok(_) => loaded += 1,
+            Err(err) => debug!(%url, error = %err, "skipping saved URL"),
+        }
+    }
+    info!(loaded, path = %path.display(), "loaded saved top URLs");
+
+    Ok(())
+}
+
+
+pub fn get_top_urls(
+    store: &dyn LinkStore,
+    counter: Counter,
+    window: Window,
+    limit: usize,
+) -> Result<Vec<(Link, u64)>, StoreError> {
+    let top_urls = store.top(limit.min(MAX_TOP_LIMIT), counter, window.since(unix_now()))?;
+    trace!(?top_urls, "ranked URLs");
+
+    Ok(top_urls)
+}
+
+
+#[instrument(skip_all, fields(by = ?query.by, window = ?query.window, limit = query.limit))]
+pub(crate) async fn top_urls(
+    store: web::Data<dyn LinkStore>,
+    settings: web::Data<Settings>,
+    query: web::Query<TopUrlsQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let top_urls = get_top_urls(store.get_ref(), query.by, query.window, query.limit)?;
+    debug!(count = top_urls.len(), "top URLs ranked");
+
+    // Only the all-time leaders are worth reloading on start.
+    if query.window == Window::All {
+        let links: Vec<Link> = top_urls.iter().map(|(link, _)| link.clone()).collect();
+        if let Err(err) = save_top_urls(&settings.top_urls_path, &links) {
+            metrics::record_persist_error();
+            error!(error = %err, path = %settings.top_urls_path.display(), "failed to save top URLs");
+        }
+    }
+
+    let top_urls: Vec<TopUrlData> = top_urls.into_iter().map(TopUrlData::from).collect();
+    Ok(HttpResponse::Ok().json(top_urls))
+}
+
+// Handler function for the per-domain metrics
+#[instrument(skip_all)]
+pub(crate) async fn domain_metrics(
+    store: web::Data<dyn LinkStore>,
+    query: web::Query<DomainMetricsQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let links = store.top(usize::MAX, query.by, query.window.since(unix_now()))?;
+    let domains = domain_counts(&links, query.limit.min(MAX_TOP_LIMIT));
+    Ok(HttpResponse::Ok().json(domains))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keygen::KeyEncoding;
+    use crate::server::{json_config, query_config};
+    use crate::store::MemoryStore;
+    use actix_web::{App,test};
+    use std::sync::Arc;
+
+    #[actix_rt::test]
+    async fn test_shorten_and_retrieve_url() {
+        // Create a test app
+        let store: Arc<dyn LinkStore> = Arc::new(MemoryStore::new());
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(store))
+                .app_data(web::Data::new(Settings::default()))
+                .route("/shorten-and-retrieve-url", web::post().to(shorten_and_retrieve_url))
+        )
+        .await;
+
+        // Make a POST request to the endpoint
+        let req_body = UrlData { url: "https://coderprog.com".to_string(), alias: None, redirect_code: None };
+        let req = test::TestRequest::post()
+            .uri("/shorten-and-retrieve-url")
+            .set_json(&req_body)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        // Check if the response is successful
+        assert!(resp.status().is_success());
+
+        // Parse the response body and verify its contents
+        let body = test::read_body(resp).await;
+        let response_data: ResponseData = serde_json::from_slice(&body).unwrap();
+        assert_eq!(response_data.original_url_received, "https://coderprog.com");
+        assert_eq!(response_data.redirect_code, RedirectCode::TemporaryRedirect);
+        // Add more assertions as needed
+    }
+
+    #[actix_rt::test]
+    async fn test_redirect_to_original() {
+        let store: Arc<dyn LinkStore> = Arc::new(MemoryStore::new());
+        store.create(Link::new("844c01eb2e56", "https://coderprog.com")).unwrap();
+        store.create(Link {
+            redirect_code: Some(RedirectCode::MovedPermanently),
+            ..Link::new("spring", "https://coderprog.com/spring")
+        }).unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(store.clone()))
+                .app_data(web::Data::new(Settings {
+                    redirect_code: RedirectCode::Found,
+                    ..Settings::default()
+                }))
+                .route("/{short_key}", web::get().to(redirect_to_original))
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/844c01eb2e56").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::FOUND);
+        assert_eq!(resp.headers().get(header::LOCATION).unwrap(), "https://coderprog.com");
+        assert_eq!(store.resolve("844c01eb2e56").unwrap().unwrap().redirects, 1);
+
+        let req = test::TestRequest::get().uri("/spring").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::MOVED_PERMANENTLY);
+
+        let req = test::TestRequest::get().uri("/unknown").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_FOUND);
+        assert!(store.events("unknown", 0, 10).unwrap().is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn test_clicks_are_recorded_as_events() {
+        let store: Arc<dyn LinkStore> = Arc::new(MemoryStore::new());
+        store.create(Link::new("844c01eb2e56", "https://coderprog.com")).unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(store.clone()))
+                .app_data(web::Data::new(Settings::default()))
+                .route("/retrieve-original-url", web::post().to(retrieve_original_url))
+                .route("/events/{short_key}", web::get().to(link_events))
+                .route("/{short_key}", web::get().to(redirect_to_original))
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/844c01eb2e56")
+            .insert_header((header::REFERER, "https://news.example"))
+            .insert_header((header::USER_AGENT, "Mozilla/5.0"))
+            .to_request();
+        test::call_service(&app, req).await;
+        let req = test::TestRequest::post()
+            .uri("/retrieve-original-url?dry_run=true")
+            .set_json(serde_json::json!({ "url": "844c01eb2e56" }))
+            .to_request();
+        test::call_service(&app, req).await;
+        let req = test::TestRequest::post()
+            .uri("/retrieve-original-url")
+            .set_json(serde_json::json!({ "url": "844c01eb2e56" }))
+            .to_request();
+        test::call_service(&app, req).await;
+
+        let req = test::TestRequest::get().uri("/events/844c01eb2e56").to_request();
+        let events: Vec<ClickEvent> = test::call_and_read_body_json(&app, req).await;
+        // The dry run is not a click.
+        assert_eq!(events.len(), 2);
+        let redirect = events.iter().find(|event| event.kind == ClickKind::Redirect).unwrap();
+        assert_eq!(redirect.referrer.as_deref(), Some("https://news.example"));
+        assert_eq!(redirect.user_agent.as_deref(), Some("Mozilla/5.0"));
+
+        let req = test::TestRequest::get().uri("/events/unknown").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn test_shorten_url_skips_expired_link() {
+        let store = MemoryStore::new();
+        let settings = Settings::default();
+        let url = "https://coderprog.com";
+        let canonical_url = canonicalize_url(url, false);
+        let short_key = generate_shortened_url_key(&canonical_url, KeyEncoding::Hex, 12);
+        store.create(Link { expires_at: Some(1), ..Link::new(&short_key, url) }).unwrap();
+
+        let link = shorten_url(&store, &settings, url, None).unwrap();
+        assert_eq!(link.key, generate_shortened_url_key(&canonical_url, KeyEncoding::Hex, 13));
+        assert_eq!(link.expires_at, None);
+        assert!(matches!(lookup_link(&store, &short_key).unwrap(), Lookup::Expired(_)));
+
+        let again = shorten_url(&store, &settings, url, None).unwrap();
+        assert_eq!((again.key, again.creations), (link.key, 2));
+    }
+
+    #[actix_rt::test]
+    async fn test_shorten_url_lengthens_colliding_key() {
+        let store = MemoryStore::new();
+        let settings = Settings::default();
+        let url = "https://coderprog.com";
+        let canonical_url = canonicalize_url(url, false);
+        let short_key = generate_shortened_url_key(&canonical_url, KeyEncoding::Hex, 12);
+        store.create(Link::new(&short_key, "https://other.example")).unwrap();
+
+        let link = shorten_url(&store, &settings, url, None).unwrap();
+        assert_eq!(link.key, generate_shortened_url_key(&canonical_url, KeyEncoding::Hex, 13));
+        assert_eq!(link.url, url);
+        assert_eq!(store.resolve(&short_key).unwrap().unwrap().url, "https://other.example");
+
+        // Re-submitting lands on the same lengthened key.
+        let again = shorten_url(&store, &settings, url, None).unwrap();
+        assert_eq!(again.key, link.key);
+        assert_eq!(again.creations, 2);
+    }
+
+    #[actix_rt::test]
+    async fn test_shorten_with_alias() {
+        let store: Arc<dyn LinkStore> = Arc::new(MemoryStore::new());
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(store.clone()))
+                .app_data(web::Data::new(Settings::default()))
+                .route("/shorten-and-retrieve-url", web::post().to(shorten_and_retrieve_url))
+        )
+        .await;
+
+        let req_body = UrlData {
+            url: "https://coderprog.com/spring".to_string(),
+            alias: Some("spring-sale".to_string()),
+            redirect_code: None,
+        };
+        let req = test::TestRequest::post()
+            .uri("/shorten-and-retrieve-url")
+            .set_json(&req_body)
+            .to_request();
+        let response_data: ResponseData = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(response_data.shortened_url, "spring-sale");
+        assert_eq!(store.resolve("spring-sale").unwrap().unwrap().url, "https://coderprog.com/spring");
+
+        // The same alias again is a conflict, even for the same URL.
+        let req = test::TestRequest::post()
+            .uri("/shorten-and-retrieve-url")
+            .set_json(&req_body)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::CONFLICT);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "alias_taken");
+
+        let req = test::TestRequest::post()
+            .uri("/shorten-and-retrieve-url")
+            .set_json(serde_json::json!({ "url": "https://coderprog.com", "alias": "top-urls" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn test_shorten_url_dedupes_canonical_spellings() {
+        let store = MemoryStore::new();
+        let settings = Settings::default();
+
+        let first = shorten_url(&store, &settings, "HTTPS://Example.com", None).unwrap();
+        let second = shorten_url(&store, &settings, "https://example.com:443/", None).unwrap();
+        assert_eq!(first.key, second.key);
+        assert_eq!(second.creations, 2);
+        // The first spelling is kept for redirects.
+        assert_eq!(second.url, "HTTPS://Example.com");
+    }
+
+    #[actix_rt::test]
+    async fn test_canonical_urls_keep_their_keys() {
+        let store = MemoryStore::new();
+        let link = shorten_url(&store, &Settings::default(), "https://coderprog.com", None).unwrap();
+        assert_eq!(link.key, "844c01eb2e56");
+    }
+
+    #[actix_rt::test]
+    async fn test_shorten_rejects_invalid_urls() {
+        let store: Arc<dyn LinkStore> = Arc::new(MemoryStore::new());
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(store.clone()))
+                .app_data(web::Data::new(Settings::default()))
+                .app_data(json_config())
+                .route("/shorten-and-retrieve-url", web::post().to(shorten_and_retrieve_url))
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/shorten-and-retrieve-url")
+            .set_json(serde_json::json!({ "url": "javascript:alert(1)" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "unsupported_scheme");
+
+        let req = test::TestRequest::post()
+            .uri("/shorten-and-retrieve-url")
+            .insert_header(header::ContentType::json())
+            .set_payload("{\"url\":")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "invalid_body");
+
+        assert!(store.list().unwrap().is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn test_retrieve_original_url() {
+        let store: Arc<dyn LinkStore> = Arc::new(MemoryStore::new());
+        store.create(Link::new("844c01eb2e56", "https://coderprog.com")).unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(store.clone()))
+                .app_data(web::Data::new(Settings::default()))
+                .route("/retrieve-original-url", web::post().to(retrieve_original_url))
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/retrieve-original-url?dry_run=true")
+            .set_json(serde_json::json!({ "url": "844c01eb2e56" }))
+            .to_request();
+        let response_data: ResponseData = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(response_data.original_url_retrieved, "https://coderprog.com");
+        assert_eq!(response_data.resolution_count, 0);
+
+        let req = test::TestRequest::post()
+            .uri("/retrieve-original-url")
+            .set_json(serde_json::json!({ "url": "844c01eb2e56" }))
+            .to_request();
+        let response_data: ResponseData = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(response_data.resolution_count, 1);
+        assert_eq!(response_data.creation_count, 1);
+
+        let req = test::TestRequest::post()
+            .uri("/retrieve-original-url")
+            .set_json(serde_json::json!({ "url": "unknown" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_FOUND);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "not_found");
+        assert_eq!(store.resolve("unknown").unwrap(), None);
+    }
+
+    #[actix_rt::test]
+    async fn test_top_urls_ranks_by_requested_counter() {
+        let store: Arc<dyn LinkStore> = Arc::new(MemoryStore::new());
+        store.create(Link { creations: 5, ..Link::new("aaa", "https://a.example") }).unwrap();
+        store.create(Link { redirects: 2, ..Link::new("bbb", "https://b.example") }).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let settings = Settings {
+            top_urls_path: dir.path().join("top_urls.txt"),
+            ..Settings::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(store.clone()))
+                .app_data(web::Data::new(settings.clone()))
+                .app_data(query_config())
+                .route("/top-urls", web::get().to(top_urls))
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/top-urls").to_request();
+        let top: Vec<TopUrlData> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(top[0].shortened_url, "bbb");
+        assert_eq!((top[0].creation_count, top[0].redirect_count), (1, 2));
+
+        let req = test::TestRequest::get().uri("/top-urls?by=creations").to_request();
+        let top: Vec<TopUrlData> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(top[0].shortened_url, "aaa");
+
+        let req = test::TestRequest::get().uri("/top-urls?window=1h&limit=10").to_request();
+        let top: Vec<TopUrlData> = test::call_and_read_body_json(&app, req).await;
+        assert!(top.is_empty());
+        store.increment("aaa", Counter::Redirects).unwrap();
+        let req = test::TestRequest::get().uri("/top-urls?window=1h&limit=10").to_request();
+        let top: Vec<TopUrlData> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(top.len(), 1);
+        assert_eq!((top[0].shortened_url.as_str(), top[0].window_count), ("aaa", 1));
+
+        let req = test::TestRequest::get().uri("/top-urls?window=2h").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::get().uri("/top-urls?by=clicks").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        // The last ranking was saved and loads back with all its counters.
+        let reloaded = MemoryStore::new();
+        load_top_urls(&settings.top_urls_path, &reloaded).unwrap();
+        let link = reloaded.resolve("https://a.example").unwrap().unwrap();
+        assert_eq!((link.creations, link.redirects), (5, 0));
+    }
+
+    #[actix_rt::test]
+    async fn test_parse_top_url_line() {
+        let link = parse_top_url_line("https://coderprog.com:8080:13:2:5").unwrap();
+        assert_eq!(link.url, "https://coderprog.com:8080");
+        assert_eq!((link.creations, link.resolutions, link.redirects), (13, 2, 5));
+
+        // Files written before the counters were split
+        let link = parse_top_url_line("https://coderprog.com:8080:13").unwrap();
+        assert_eq!(link.url, "https://coderprog.com:8080");
+        assert_eq!((link.creations, link.resolutions, link.redirects), (13, 0, 0));
+    }
+
+    #[actix_rt::test]
+    async fn test_domain_metrics() {
+        let store: Arc<dyn LinkStore> = Arc::new(MemoryStore::new());
+        store.create(Link { redirects: 4, ..Link::new("aaa", "https://coderprog.com") }).unwrap();
+        store.create(Link { redirects: 3, ..Link::new("bbb", "https://Coderprog.com:443/x") }).unwrap();
+        store.create(Link { redirects: 5, ..Link::new("ccc", "https://other.example/") }).unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(store.clone()))
+                .app_data(query_config())
+                .route("/metrics/domains", web::get().to(domain_metrics))
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/metrics/domains?limit=1").to_request();
+        let domains: Vec<crate::domains::DomainCount> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(domains.len(), 1);
+        assert_eq!((domains[0].domain.as_str(), domains[0].count), ("coderprog.com", 7));
+
+        store.increment("ccc", Counter::Redirects).unwrap();
+        let req = test::TestRequest::get().uri("/metrics/domains?window=1h").to_request();
+        let domains: Vec<crate::domains::DomainCount> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(domains.len(), 1);
+        assert_eq!((domains[0].domain.as_str(), domains[0].count), ("other.example", 1));
+    }
+}
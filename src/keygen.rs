@@ -0,0 +1,160 @@
+//! Turns a URL into a short key.
+//!
+//! Every encoding spells out the same SHA3-256 digest of the URL; a key is a
+//! prefix of that spelling, so a longer key for the same URL always extends
+//! the shorter one.
+
+use std::fmt;
+use std::str::FromStr;
+
+use tiny_keccak::{Hasher, Sha3};
+
+const BASE62_ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+/// Base62 without `0`, `O`, `I` and `l`, which are easily misread.
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeyEncoding {
+    /// Lowercase hex of the digest bytes, in order.
+    #[default]
+    Hex,
+    Base62,
+    Base58,
+}
+
+impl KeyEncoding {
+    /// Characters needed to spell out a whole 256-bit digest.
+    pub fn max_len(self) -> usize {
+        match self {
+            KeyEncoding::Hex => 64,
+            KeyEncoding::Base62 => 43,
+            KeyEncoding::Base58 => 44,
+        }
+    }
+}
+
+impl FromStr for KeyEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<KeyEncoding, String> {
+        match s {
+            "hex" => Ok(KeyEncoding::Hex),
+            "base62" => Ok(KeyEncoding::Base62),
+            "base58" => Ok(KeyEncoding::Base58),
+            other => Err(format!("unknown key encoding {}, expected hex, base62 or base58", other)),
+        }
+    }
+}
+
+impl fmt::Display for KeyEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            KeyEncoding::Hex => "hex",
+            KeyEncoding::Base62 => "base62",
+            KeyEncoding::Base58 => "base58",
+        })
+    }
+}
+
+/// How generated keys are spelled and how long they start out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyFormat {
+    pub encoding: KeyEncoding,
+    pub length: usize,
+}
+
+impl KeyFormat {
+    pub fn new(encoding: KeyEncoding, length: usize) -> Result<KeyFormat, String> {
+        if length == 0 || length > encoding.max_len() {
+            return Err(format!(
+                "key length must be between 1 and {} for {}",
+                encoding.max_len(),
+                encoding
+            ));
+        }
+        Ok(KeyFormat { encoding, length })
+    }
+}
+
+impl Default for KeyFormat {
+    /// 12 hex characters, the format keys have always had.
+    fn default() -> KeyFormat {
+        KeyFormat {
+            encoding: KeyEncoding::Hex,
+            length: 12,
+        }
+    }
+}
+
+/// Returns the first `length` characters of the URL's digest in `encoding`.
+pub fn generate_shortened_url_key(original_url: &str, encoding: KeyEncoding, length: usize) -> String {
+    let mut hasher = Sha3::v256();
+    hasher.update(original_url.as_bytes());
+    let mut result = [0u8; 32];
+    hasher.finalize(&mut result);
+
+    let mut shortened_url = encode(result, encoding);
+    shortened_url.truncate(length);
+    shortened_url
+}
+
+fn encode(mut digest: [u8; 32], encoding: KeyEncoding) -> String {
+    let alphabet = match encoding {
+        KeyEncoding::Hex => {
+            return digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+        }
+        KeyEncoding::Base62 => BASE62_ALPHABET,
+        KeyEncoding::Base58 => BASE58_ALPHABET,
+    };
+
+    // Emit least significant digits first: unlike the leading digit, each of
+    // them is uniformly distributed, so short prefixes use the whole alphabet.
+    let radix = alphabet.len() as u32;
+    let mut encoded = String::with_capacity(encoding.max_len());
+    for _ in 0..encoding.max_len() {
+        let mut remainder = 0u32;
+        for byte in digest.iter_mut() {
+            let acc = (remainder << 8) | u32::from(*byte);
+            *byte = (acc / radix) as u8;
+            remainder = acc % radix;
+        }
+        encoded.push(alphabet[remainder as usize] as char);
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_keys_are_unchanged() {
+        let key = generate_shortened_url_key("https://coderprog.com", KeyEncoding::Hex, 12);
+        assert_eq!(key, "844c01eb2e56");
+    }
+
+    #[test]
+    fn longer_keys_extend_shorter_ones() {
+        for encoding in [KeyEncoding::Hex, KeyEncoding::Base62, KeyEncoding::Base58] {
+            let short = generate_shortened_url_key("https://coderprog.com", encoding, 7);
+            let long = generate_shortened_url_key("https://coderprog.com", encoding, 8);
+            assert_eq!(short.len(), 7);
+            assert!(long.starts_with(&short));
+        }
+    }
+
+    #[test]
+    fn base58_avoids_ambiguous_characters() {
+        let key = generate_shortened_url_key("https://coderprog.com", KeyEncoding::Base58, 44);
+        assert_eq!(key.len(), 44);
+        assert!(!key.contains(['0', 'O', 'I', 'l']));
+        assert!(key.bytes().all(|b| b.is_ascii_alphanumeric()));
+    }
+
+    #[test]
+    fn rejects_impossible_lengths() {
+        assert!(KeyFormat::new(KeyEncoding::Base62, 0).is_err());
+        assert!(KeyFormat::new(KeyEncoding::Base62, 44).is_err());
+        assert!(KeyFormat::new(KeyEncoding::Base62, 7).is_ok());
+    }
+}
@@ -0,0 +1,21 @@
+//! A URL shortener: key generation, link storage and the actix-web routes
+//! serving them, usable as a standalone server or mounted into another app
+//! with [`server::configure`].
+
+mod alias;
+pub mod api;
+mod canonical;
+pub mod cli;
+pub mod config;
+pub mod domains;
+pub mod error;
+mod events;
+pub mod handlers;
+pub mod keygen;
+pub mod logging;
+pub mod metrics;
+pub mod server;
+pub mod settings;
+pub mod store;
+mod validate;
+pub mod window;
@@ -0,0 +1,88 @@
+//! Log output and per-request tracing.
+//!
+//! Every request runs in a root span carrying a generated `request_id`, which
+//! is also returned to the client in the `x-request-id` header. Destination
+//! URLs and key listings are only logged at `debug` and below.
+
+use std::future::Future;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::HttpMessage;
+use tracing_actix_web::RequestId;
+use tracing_subscriber::EnvFilter;
+
+/// Filter used when `URLSHORTNER_LOG` is not set.
+const DEFAULT_FILTER: &str = "info";
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format {:?} (expected text or json)", s)),
+        }
+    }
+}
+
+/// Installs the global subscriber. `filter` uses the `RUST_LOG` directive
+/// syntax, e.g. `info,urlshortner=debug`.
+pub fn init(format: LogFormat, filter: Option<&str>) {
+    let filter = EnvFilter::try_new(filter.unwrap_or(DEFAULT_FILTER)).unwrap_or_else(|err| {
+        eprintln!("Invalid log filter, using {:?}: {}", DEFAULT_FILTER, err);
+        EnvFilter::new(DEFAULT_FILTER)
+    });
+    // stderr, so subcommand output on stdout stays machine readable.
+    let builder = tracing_subscriber::fmt().with_env_filter(filter).with_writer(std::io::stderr);
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().flatten_event(true).with_current_span(true).init(),
+    }
+}
+
+/// `wrap_fn` middleware echoing the request id assigned by `TracingLogger`,
+/// so clients can quote it when reporting a problem.
+pub fn request_id_header<S, B>(
+    req: ServiceRequest,
+    srv: &S,
+) -> impl Future<Output = Result<ServiceResponse<B>, actix_web::Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let request_id = req.extensions().get::<RequestId>().copied();
+    let fut = srv.call(req);
+    async move {
+        let mut res = fut.await?;
+        if let Some(request_id) = request_id {
+            if let Ok(value) = HeaderValue::from_str(&request_id.to_string()) {
+                res.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+            }
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_log_formats() {
+        assert_eq!("json".parse::<LogFormat>(), Ok(LogFormat::Json));
+        assert_eq!("Text".parse::<LogFormat>(), Ok(LogFormat::Text));
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+}
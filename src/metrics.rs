@@ -0,0 +1,152 @@
+//! Operational metrics, exposed in the Prometheus text format at `/metrics`.
+
+use std::future::Future;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Instant;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
+use lazy_static::lazy_static;
+use parking_lot::{ReentrantMutex, ReentrantMutexGuard};
+use prometheus::{
+    register_histogram_vec_with_registry, register_int_counter_vec_with_registry,
+    register_int_counter_with_registry, register_int_gauge_vec_with_registry, Encoder, HistogramVec,
+    IntCounter, IntCounterVec, IntGaugeVec, Registry, TextEncoder,
+};
+
+use crate::error::ApiError;
+use crate::store::LinkStore;
+
+lazy_static! {
+    static ref REGISTRY: Registry = Registry::new_custom(Some("urlshortner".to_string()), None).unwrap();
+    static ref HTTP_REQUESTS: IntCounterVec = register_int_counter_vec_with_registry!(
+        "http_requests_total",
+        "HTTP requests handled, by route and status",
+        &["route", "method", "status"],
+        REGISTRY
+    )
+    .unwrap();
+    static ref HTTP_DURATION: HistogramVec = register_histogram_vec_with_registry!(
+        "http_request_duration_seconds",
+        "Time spent handling HTTP requests, by route",
+        &["route", "method"],
+        REGISTRY
+    )
+    .unwrap();
+    static ref STORE_LINKS: IntGaugeVec = register_int_gauge_vec_with_registry!(
+        "store_links",
+        "Links currently stored, by the path the shortener is mounted at",
+        &["mount"],
+        REGISTRY
+    )
+    .unwrap();
+    static ref LOCK_WAIT: HistogramVec = register_histogram_vec_with_registry!(
+        "lock_wait_seconds",
+        "Time spent waiting to acquire store locks",
+        &["lock"],
+        vec![0.000_001, 0.000_01, 0.000_1, 0.001, 0.01, 0.1, 1.0],
+        REGISTRY
+    )
+    .unwrap();
+    static ref PERSIST_ERRORS: IntCounter = register_int_counter_with_registry!(
+        "top_urls_persist_errors_total",
+        "Failed writes of the top URLs file",
+        REGISTRY
+    )
+    .unwrap();
+}
+
+/// Name of the resource serving short keys, whatever prefix it is mounted under.
+pub const REDIRECT_ROUTE: &str = "redirect";
+
+/// Route label for a request: the pattern it matched, so every short key
+/// counts as `redirect` instead of producing a series per key.
+fn route_label<'a>(name: Option<&'a str>, pattern: Option<&'a str>) -> &'a str {
+    match (name, pattern) {
+        (Some(REDIRECT_ROUTE), _) => REDIRECT_ROUTE,
+        (_, Some(pattern)) => pattern,
+        (_, None) => "unmatched",
+    }
+}
+
+/// `wrap_fn` middleware counting and timing every request.
+pub fn track<S, B>(req: ServiceRequest, srv: &S) -> impl Future<Output = Result<ServiceResponse<B>, actix_web::Error>>
+where
+    S: actix_web::dev::Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let started = Instant::now();
+    let method = req.method().to_string();
+    let fut = srv.call(req);
+    async move {
+        let res = fut.await?;
+        let route = route_label(res.request().match_name(), res.request().match_pattern().as_deref()).to_string();
+        HTTP_DURATION
+            .with_label_values(&[&route, &method])
+            .observe(started.elapsed().as_secs_f64());
+        HTTP_REQUESTS
+            .with_label_values(&[&route, &method, res.status().as_str()])
+            .inc();
+        Ok(res)
+    }
+}
+
+/// Locks `mutex`, recording how long it took under the `lock` label.
+pub fn timed_lock<'a, T>(mutex: &'a Mutex<T>, lock: &str) -> MutexGuard<'a, T> {
+    let started = Instant::now();
+    let guard = mutex.lock().unwrap();
+    LOCK_WAIT.with_label_values(&[lock]).observe(started.elapsed().as_secs_f64());
+    guard
+}
+
+/// `timed_lock` for a lock the holding thread may take again.
+pub fn timed_reentrant_lock<'a, T>(mutex: &'a ReentrantMutex<T>, lock: &str) -> ReentrantMutexGuard<'a, T> {
+    let started = Instant::now();
+    let guard = mutex.lock();
+    LOCK_WAIT.with_label_values(&[lock]).observe(started.elapsed().as_secs_f64());
+    guard
+}
+
+pub fn record_persist_error() {
+    PERSIST_ERRORS.inc();
+}
+
+/// Mount label for a request to `/metrics`: the path in front of it, or `/`
+/// at the root. Each mount has its own store, so each gets its own series.
+fn mount_label(path: &str) -> &str {
+    match path.strip_suffix("/metrics").unwrap_or(path) {
+        "" => "/",
+        mount => mount,
+    }
+}
+
+// Handler function for the Prometheus scrape endpoint
+pub async fn export(req: HttpRequest, store: web::Data<dyn LinkStore>) -> Result<HttpResponse, ApiError> {
+    STORE_LINKS.with_label_values(&[mount_label(req.path())]).set(store.len()? as i64);
+
+    let mut buffer = Vec::new();
+    let encoder = TextEncoder::new();
+    encoder
+        .encode(&REGISTRY.gather(), &mut buffer)
+        .expect("text encoding into a Vec cannot fail");
+    Ok(HttpResponse::Ok().content_type(encoder.format_type()).body(buffer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_routes_by_pattern() {
+        assert_eq!(route_label(Some(REDIRECT_ROUTE), Some("/redirect/{short_key}")), "redirect");
+        assert_eq!(route_label(None, Some("/top-urls")), "/top-urls");
+        assert_eq!(route_label(None, None), "unmatched");
+    }
+
+    #[test]
+    fn labels_mounts_by_path() {
+        assert_eq!(mount_label("/metrics"), "/");
+        assert_eq!(mount_label("/go/metrics"), "/go");
+    }
+}
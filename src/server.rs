@@ -0,0 +1,334 @@
+//! Route registration and the standalone server.
+
+use std::sync::Arc;
+
+use actix_web::http::header;
+use actix_web::middleware::DefaultHeaders;
+use actix_web::{web, Scope};
+use tracing::info;
+use tracing_actix_web::TracingLogger;
+
+use crate::api;
+use crate::config::{Config, StorageBackend, StorageConfig};
+use crate::error::ApiError;
+use crate::handlers::{
+    domain_metrics, landing_page, link_events, load_top_urls, redirect_to_original, retrieve_original_url,
+    shorten_and_retrieve_url, top_urls,
+};
+use crate::logging;
+use crate::metrics;
+use crate::settings::Settings;
+use crate::store::{LinkStore, MemoryStore, SqliteStore, WalStore};
+
+/// Opens the configured storage backend.
+pub fn open_store(config: &StorageConfig) -> std::io::Result<Arc<dyn LinkStore>> {
+    match config.backend {
+        StorageBackend::Memory => {
+            let store = MemoryStore::new();
+            // Load top URLs from file when program starts
+            load_top_urls(&config.top_urls_path, &store)?;
+            Ok(Arc::new(store))
+        }
+        StorageBackend::Wal => {
+            // Replays the snapshot and log, so top_urls.txt is not needed here.
+            let store = WalStore::open(&config.wal_dir, config.snapshot_every).map_err(std::io::Error::other)?;
+            Ok(Arc::new(store))
+        }
+        StorageBackend::Sqlite => {
+            let store = SqliteStore::open(&config.database).map_err(std::io::Error::other)?;
+            Ok(Arc::new(store))
+        }
+    }
+}
+
+/// Makes malformed JSON bodies answer with the same error body as every other failure.
+pub fn json_config() -> web::JsonConfig {
+    web::JsonConfig::default()
+        .error_handler(|err, _req| ApiError::InvalidBody(err.to_string()).into())
+}
+
+/// Same as `json_config`, for query strings.
+pub fn query_config() -> web::QueryConfig {
+    web::QueryConfig::default()
+        .error_handler(|err, _req| ApiError::InvalidQuery(err.to_string()).into())
+}
+
+/// Registers the shortener's routes, with the optional ones chosen by `settings`.
+///
+/// The handlers read `web::Data<dyn LinkStore>` and `web::Data<Settings>`
+/// from the enclosing app or scope; [`service`] provides both.
+pub fn configure(cfg: &mut web::ServiceConfig, settings: &Settings) {
+    api::configure(cfg);
+    // The RPC-style routes predate /api/v1/links and are kept for existing clients.
+    cfg.service(
+        web::resource("/shorten-and-retrieve-url")
+            .wrap(deprecated())
+            .route(web::post().to(shorten_and_retrieve_url)),
+    )
+    .service(
+        web::resource("/retrieve-original-url")
+            .wrap(deprecated())
+            .route(web::post().to(retrieve_original_url)),
+    )
+    .route("/top-urls", web::get().to(top_urls))
+        .route("/events/{short_key}", web::get().to(link_events))
+        .route("/metrics", web::get().to(metrics::export));
+    if settings.domain_metrics {
+        cfg.route("/metrics/domains", web::get().to(domain_metrics));
+    }
+    if settings.landing_url.is_some() {
+        cfg.route("/", web::get().to(landing_page));
+    }
+    // Must stay last so that, mounted at the root, it does not shadow the fixed routes above
+    cfg.service(
+        web::resource(format!("{}/{{short_key}}", settings.redirect_prefix))
+            .name(metrics::REDIRECT_ROUTE)
+            .route(web::get().to(redirect_to_original)),
+    );
+}
+
+/// Marks responses of a deprecated route and points at its replacement.
+///
+/// The link is relative so it resolves under whatever path the shortener is
+/// mounted at; that holds as long as the deprecated routes sit one segment
+/// below the mount, as `/shorten-and-retrieve-url` does.
+fn deprecated() -> DefaultHeaders {
+    DefaultHeaders::new()
+        .add(("Deprecation", "true"))
+        .add((header::LINK, "<api/v1/links>; rel=\"successor-version\""))
+}
+
+/// Routes bound to their own store and settings, for `App::configure` or
+/// `Scope::configure`. Each call carries its own state, so the shortener can
+/// be mounted several times in one app without the mounts seeing each other's
+/// links. The Prometheus registry behind `/metrics` is process-wide; the
+/// `store_links` gauge is labelled with the mount path so mounts do not
+/// overwrite each other's count.
+pub fn service(store: Arc<dyn LinkStore>, settings: Settings) -> impl FnOnce(&mut web::ServiceConfig) {
+    move |cfg| {
+        cfg.app_data(web::Data::from(store))
+            .app_data(web::Data::new(settings.clone()))
+            .app_data(json_config())
+            .app_data(query_config());
+        configure(cfg, &settings);
+    }
+}
+
+/// The shortener mounted under `path`, e.g. `/go`:
+///
+/// ```no_run
+/// use std::sync::Arc;
+/// use actix_web::App;
+/// use urlshortner::server::scope;
+/// use urlshortner::settings::Settings;
+/// use urlshortner::store::MemoryStore;
+///
+/// let app = App::new()
+///     .service(scope("/go", Arc::new(MemoryStore::new()), Settings::default()))
+///     .service(scope("/internal", Arc::new(MemoryStore::new()), Settings::default()));
+/// ```
+pub fn scope(path: &str, store: Arc<dyn LinkStore>, settings: Settings) -> Scope {
+    web::scope(path).configure(service(store, settings))
+}
+
+/// Runs the HTTP server until it is shut down.
+pub async fn serve(config: Config, settings: Settings, store: Arc<dyn LinkStore>) -> std::io::Result<()> {
+    info!(address = %config.server.bind, storage = %config.storage.backend, "starting server");
+
+    let server = actix_web::HttpServer::new(move || {
+        actix_web::App::new()
+            .wrap_fn(metrics::track)
+            .wrap_fn(logging::request_id_header)
+            .wrap(TracingLogger::default())
+            .configure(service(store.clone(), settings.clone()))
+    });
+    let server = match config.server.workers {
+        Some(workers) => server.workers(workers),
+        None => server,
+    };
+    server.bind(&config.server.bind)?.run().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{Link, RedirectCode};
+    use actix_web::{test, App};
+
+    /// The full app over a one-link memory store. `/top-urls` saves into
+    /// `dir`, so tests never write to the working directory.
+    fn app_with(
+        settings: Settings,
+        dir: &tempfile::TempDir,
+    ) -> App<
+        impl actix_web::dev::ServiceFactory<
+            actix_web::dev::ServiceRequest,
+            Config = (),
+            Response = actix_web::dev::ServiceResponse<impl actix_web::body::MessageBody>,
+            Error = actix_web::Error,
+            InitError = (),
+        >,
+    > {
+        let store: Arc<dyn LinkStore> = Arc::new(MemoryStore::new());
+        store.create(Link::new("aaa", "https://coderprog.com")).unwrap();
+        let settings = Settings { top_urls_path: dir.path().join("top_urls.txt"), ..settings };
+        App::new()
+            .wrap_fn(metrics::track)
+            .wrap_fn(logging::request_id_header)
+            .wrap(TracingLogger::default())
+            .configure(service(store, settings))
+    }
+
+    #[actix_rt::test]
+    async fn test_redirect_prefix_and_landing_page() {
+        let settings = Settings {
+            redirect_prefix: "/redirect".to_string(),
+            landing_url: Some("https://example.com".to_string()),
+            domain_metrics: false,
+            ..Settings::default()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let app = test::init_service(app_with(settings, &dir)).await;
+
+        let req = test::TestRequest::get().uri("/redirect/aaa").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 307);
+        assert_eq!(resp.headers().get(header::LOCATION).unwrap(), "https://coderprog.com");
+
+        let req = test::TestRequest::get().uri("/aaa").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 404);
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 307);
+        assert_eq!(resp.headers().get(header::LOCATION).unwrap(), "https://example.com");
+
+        let req = test::TestRequest::get().uri("/metrics/domains").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 404);
+    }
+
+    #[actix_rt::test]
+    async fn test_default_routes() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = test::init_service(app_with(Settings::default(), &dir)).await;
+
+        let req = test::TestRequest::get().uri("/aaa").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 307);
+        let req = test::TestRequest::get().uri("/").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 404);
+        let req = test::TestRequest::get().uri("/metrics/domains").to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+    }
+
+    #[actix_rt::test]
+    async fn test_metrics_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = test::init_service(app_with(Settings::default(), &dir)).await;
+
+        let req = test::TestRequest::get().uri("/missing-key").to_request();
+        test::call_service(&app, req).await;
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains(r#"urlshortner_store_links{mount="/"} 1"#));
+        assert!(body.contains(r#"urlshortner_http_requests_total{method="GET",route="redirect",status="404"}"#));
+        assert!(body.contains("urlshortner_lock_wait_seconds_bucket"));
+    }
+
+    #[actix_rt::test]
+    async fn test_request_id_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = test::init_service(app_with(Settings::default(), &dir)).await;
+
+        let req = test::TestRequest::get().uri("/missing-key").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 404);
+        let request_id = resp.headers().get(logging::REQUEST_ID_HEADER).unwrap();
+        assert_eq!(request_id.to_str().unwrap().len(), 36);
+    }
+
+    #[actix_rt::test]
+    async fn test_independent_scopes() {
+        let go: Arc<dyn LinkStore> = Arc::new(MemoryStore::new());
+        let internal: Arc<dyn LinkStore> = Arc::new(MemoryStore::new());
+        go.create(Link::new("aaa", "https://coderprog.com")).unwrap();
+        let internal_settings = Settings { redirect_code: RedirectCode::MovedPermanently, ..Settings::default() };
+        let app = test::init_service(
+            App::new()
+                .service(scope("/go", go.clone(), Settings::default()))
+                .service(scope("/internal", internal.clone(), internal_settings)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/go/aaa").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 307);
+        let req = test::TestRequest::get().uri("/internal/aaa").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 404);
+
+        let req = test::TestRequest::post()
+            .uri("/internal/shorten-and-retrieve-url")
+            .set_json(serde_json::json!({ "url": "https://coderprog.com" }))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        let req = test::TestRequest::get().uri("/internal/844c01eb2e56").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 301);
+        assert!(go.resolve("844c01eb2e56").unwrap().is_none());
+        assert_eq!(go.resolve("aaa").unwrap().unwrap().redirects, 1);
+        assert_eq!(internal.resolve("844c01eb2e56").unwrap().unwrap().redirects, 1);
+
+        // Scraping one mount does not overwrite the other's link count.
+        go.create(Link::new("bbb", "https://b.example")).unwrap();
+        for path in ["/go/metrics", "/internal/metrics"] {
+            test::call_service(&app, test::TestRequest::get().uri(path).to_request()).await;
+        }
+        let req = test::TestRequest::get().uri("/go/metrics").to_request();
+        let body = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+        assert!(body.contains(r#"urlshortner_store_links{mount="/go"} 2"#));
+        assert!(body.contains(r#"urlshortner_store_links{mount="/internal"} 1"#));
+
+        // Each scope answers bad input with its own JSON error config.
+        let req = test::TestRequest::post()
+            .uri("/go/shorten-and-retrieve-url")
+            .insert_header((header::CONTENT_TYPE, "application/json"))
+            .set_payload("{")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "invalid_body");
+    }
+
+    #[actix_rt::test]
+    async fn test_legacy_routes_are_marked_deprecated() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = test::init_service(app_with(Settings::default(), &dir)).await;
+
+        let req = test::TestRequest::post()
+            .uri("/shorten-and-retrieve-url")
+            .set_json(serde_json::json!({ "url": "https://coderprog.com" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        assert_eq!(resp.headers().get("deprecation").unwrap(), "true");
+        assert_eq!(resp.headers().get(header::LINK).unwrap(), "<api/v1/links>; rel=\"successor-version\"");
+
+        let req = test::TestRequest::get().uri("/top-urls").to_request();
+        assert!(test::call_service(&app, req).await.headers().get("deprecation").is_none());
+    }
+
+    #[actix_rt::test]
+    async fn test_deprecation_link_resolves_under_a_scope() {
+        let app = test::init_service(App::new().service(scope("/go", Arc::new(MemoryStore::new()), Settings::default()))).await;
+
+        let uri = "http://localhost/go/shorten-and-retrieve-url";
+        let req = test::TestRequest::post().uri(uri).set_json(serde_json::json!({ "url": "https://coderprog.com" })).to_request();
+        let resp = test::call_service(&app, req).await;
+        let link = resp.headers().get(header::LINK).unwrap().to_str().unwrap();
+        let target = link.trim_start_matches('<').split_once('>').unwrap().0;
+        let successor = url::Url::parse(uri).unwrap().join(target).unwrap();
+        assert_eq!(successor.path(), "/go/api/v1/links");
+
+        let req = test::TestRequest::get().uri(successor.path()).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 200);
+    }
+}
@@ -0,0 +1,56 @@
+//! Server-wide behaviour shared by all handlers through `web::Data<Settings>`.
+
+use std::path::PathBuf;
+
+use crate::keygen::KeyFormat;
+use crate::store::{Link, RedirectCode};
+
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// Redirect status for links that do not carry their own.
+    pub redirect_code: RedirectCode,
+    /// Encoding and starting length of generated keys.
+    pub key_format: KeyFormat,
+    /// Ignore utm_* and similar tracking parameters when deduplicating URLs.
+    pub strip_tracking_params: bool,
+    /// Schemes a destination URL may use.
+    pub allowed_schemes: Vec<String>,
+    /// Longest destination URL accepted, in bytes.
+    pub max_url_length: usize,
+    /// Where `/top-urls` saves its last ranking for the memory backend to reload.
+    pub top_urls_path: PathBuf,
+    /// Salt for hashing client addresses in click events. Defaults to a random
+    /// value, so hashes are only comparable within one run unless it is set.
+    pub ip_hash_salt: String,
+    /// Path short keys are served under: empty for `/{key}`, or e.g.
+    /// `/redirect` for `/redirect/{key}`.
+    pub redirect_prefix: String,
+    /// Where `GET /` redirects to; `/` is not routed when unset.
+    pub landing_url: Option<String>,
+    /// Serve `/metrics/domains`.
+    pub domain_metrics: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            redirect_code: RedirectCode::default(),
+            key_format: KeyFormat::default(),
+            strip_tracking_params: false,
+            allowed_schemes: vec!["http".to_string(), "https".to_string()],
+            max_url_length: 2048,
+            top_urls_path: PathBuf::from("top_urls.txt"),
+            ip_hash_salt: format!("{:016x}", rand::random::<u64>()),
+            redirect_prefix: String::new(),
+            landing_url: None,
+            domain_metrics: true,
+        }
+    }
+}
+
+impl Settings {
+    /// The status code a redirect for `link` should use.
+    pub fn redirect_code_for(&self, link: &Link) -> RedirectCode {
+        link.redirect_code.unwrap_or(self.redirect_code)
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::metrics::timed_lock;
+use super::{
+    rank_links, unix_now, ClickEvent, Counter, Link, LinkStore, LinkUpdate, StoreError, StoreResult, BUCKET_RETENTION_SECS,
+    BUCKET_SECS,
+};
+
+/// Per key and counter, the count of every bucket, keyed by bucket number
+/// (`time / BUCKET_SECS`).
+type Buckets = HashMap<(String, Counter), BTreeMap<u64, u64>>;
+
+/// One bucket count, as written to snapshots.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct Bucket {
+    pub key: String,
+    pub counter: Counter,
+    pub bucket: u64,
+    pub count: u64,
+}
+
+/// Keeps every link in a process-local map; nothing survives a restart.
+#[derive(Default)]
+pub struct MemoryStore {
+    links: Mutex<HashMap<String, Link>>,
+    /// Click events per key, oldest first.
+    events: Mutex<HashMap<String, Vec<ClickEvent>>>,
+    buckets: Mutex<Buckets>,
+    /// When each deleted key was last deleted.
+    tombstones: Mutex<HashMap<String, u64>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+
+    /// `increment` as if it happened at `at`, for replaying logged increments.
+    pub(crate) fn increment_at(&self, key: &str, counter: Counter, at: u64) -> StoreResult<Option<u64>> {
+        let mut links = timed_lock(&self.links, "memory.links");
+        let Some(link) = links.get_mut(key) else {
+            return Ok(None);
+        };
+        let count = link.count_mut(counter);
+        *count += 1;
+
+        let mut buckets = timed_lock(&self.buckets, "memory.buckets");
+        let counts = buckets.entry((key.to_string(), counter)).or_default();
+        *counts.entry(at / BUCKET_SECS).or_insert(0) += 1;
+        // Drop whatever no window reaches any more.
+        let oldest = at.saturating_sub(BUCKET_RETENTION_SECS) / BUCKET_SECS;
+        *counts = counts.split_off(&oldest);
+
+        Ok(Some(*count))
+    }
+
+    /// `delete` as if it happened at `at`, for replaying logged deletes.
+    pub(crate) fn delete_at(&self, key: &str, at: u64) -> StoreResult<bool> {
+        let mut links = timed_lock(&self.links, "memory.links");
+        timed_lock(&self.buckets, "memory.buckets").retain(|(bucket_key, _), _| bucket_key != key);
+        if links.remove(key).is_none() {
+            return Ok(false);
+        }
+        timed_lock(&self.tombstones, "memory.tombstones").insert(key.to_string(), at);
+        Ok(true)
+    }
+
+    pub(crate) fn tombstones(&self) -> HashMap<String, u64> {
+        timed_lock(&self.tombstones, "memory.tombstones").clone()
+    }
+
+    /// Puts back tombstones saved by `tombstones`.
+    pub(crate) fn restore_tombstones(&self, tombstones: HashMap<String, u64>) {
+        timed_lock(&self.tombstones, "memory.tombstones").extend(tombstones);
+    }
+
+    pub(crate) fn buckets(&self) -> Vec<Bucket> {
+        let buckets = timed_lock(&self.buckets, "memory.buckets");
+        buckets
+            .iter()
+            .flat_map(|((key, counter), counts)| {
+                counts.iter().map(move |(bucket, count)| Bucket {
+                    key: key.clone(),
+                    counter: *counter,
+                    bucket: *bucket,
+                    count: *count,
+                })
+            })
+            .collect()
+    }
+
+    /// Puts back a bucket saved by `buckets`; lifetime counters are not touched.
+    pub(crate) fn restore_bucket(&self, bucket: Bucket) {
+        let mut buckets = timed_lock(&self.buckets, "memory.buckets");
+        buckets
+            .entry((bucket.key, bucket.counter))
+            .or_default()
+            .insert(bucket.bucket, bucket.count);
+    }
+}
+
+impl LinkStore for MemoryStore {
+    fn create(&self, link: Link) -> StoreResult<Link> {
+        let mut links = timed_lock(&self.links, "memory.links");
+        if links.contains_key(&link.key) {
+            return Err(StoreError::KeyExists(link.key));
+        }
+        links.insert(link.key.clone(), link.clone());
+        Ok(link)
+    }
+
+    fn resolve(&self, key: &str) -> StoreResult<Option<Link>> {
+        Ok(timed_lock(&self.links, "memory.links").get(key).cloned())
+    }
+
+    fn increment(&self, key: &str, counter: Counter) -> StoreResult<Option<u64>> {
+        self.increment_at(key, counter, unix_now())
+    }
+
+    fn list(&self) -> StoreResult<Vec<Link>> {
+        Ok(timed_lock(&self.links, "memory.links").values().cloned().collect())
+    }
+
+    fn update(&self, key: &str, update: LinkUpdate) -> StoreResult<Option<Link>> {
+        let mut links = timed_lock(&self.links, "memory.links");
+        Ok(links.get_mut(key).map(|link| {
+            update.apply(link);
+            link.clone()
+        }))
+    }
+
+    fn delete(&self, key: &str) -> StoreResult<bool> {
+        self.delete_at(key, unix_now())
+    }
+
+    fn deleted_at(&self, key: &str) -> StoreResult<Option<u64>> {
+        Ok(timed_lock(&self.tombstones, "memory.tombstones").get(key).copied())
+    }
+
+    fn transaction(&self, work: &mut dyn FnMut(&dyn LinkStore) -> StoreResult<()>) -> StoreResult<()> {
+        work(self)
+    }
+
+    fn top(&self, limit: usize, counter: Counter, since: Option<u64>) -> StoreResult<Vec<(Link, u64)>> {
+        let links = timed_lock(&self.links, "memory.links");
+        let mut ranked: Vec<(Link, u64)> = match since {
+            None => links
+                .values()
+                .map(|link| (link.clone(), link.count(counter)))
+                .collect(),
+            Some(since) => {
+                let buckets = timed_lock(&self.buckets, "memory.buckets");
+                buckets
+                    .iter()
+                    .filter(|((_, bucket_counter), _)| *bucket_counter == counter)
+                    .filter_map(|((key, _), counts)| {
+                        let count: u64 = counts.range(since / BUCKET_SECS..).map(|(_, count)| count).sum();
+                        let link = links.get(key)?;
+                        (count > 0).then(|| (link.clone(), count))
+                    })
+                    .collect()
+            }
+        };
+        rank_links(&mut ranked);
+        ranked.truncate(limit);
+        Ok(ranked)
+    }
+
+    fn record_event(&self, event: ClickEvent) -> StoreResult<()> {
+        let mut events = timed_lock(&self.events, "memory.events");
+        events.entry(event.key.clone()).or_default().push(event);
+        Ok(())
+    }
+
+    fn events(&self, key: &str, since: u64, limit: usize) -> StoreResult<Vec<ClickEvent>> {
+        let events = timed_lock(&self.events, "memory.events");
+        Ok(events
+            .get(key)
+            .into_iter()
+            .flat_map(|events| events.iter().rev())
+            .filter(|event| event.at >= since)
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::RedirectCode;
+    use crate::store::ClickKind;
+
+    fn click(key: &str, at: u64) -> ClickEvent {
+        ClickEvent {
+            key: key.to_string(),
+            at,
+            kind: ClickKind::Redirect,
+            referrer: None,
+            user_agent: None,
+            client_ip_hash: None,
+        }
+    }
+
+    #[test]
+    fn events_are_returned_newest_first() {
+        let store = MemoryStore::new();
+        for at in [10, 20, 30] {
+            store.record_event(click("a", at)).unwrap();
+        }
+        store.record_event(click("b", 40)).unwrap();
+
+        let events = store.events("a", 15, 10).unwrap();
+        let times: Vec<u64> = events.iter().map(|event| event.at).collect();
+        assert_eq!(times, [30, 20]);
+        assert_eq!(store.events("a", 0, 1).unwrap()[0].at, 30);
+        assert!(store.events("missing", 0, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn create_rejects_existing_key() {
+        let store = MemoryStore::new();
+        store.create(Link::new("abc", "https://a.example")).unwrap();
+        let err = store.create(Link::new("abc", "https://b.example")).unwrap_err();
+        assert!(matches!(err, StoreError::KeyExists(_)));
+        assert_eq!(store.resolve("abc").unwrap().unwrap().url, "https://a.example");
+    }
+
+    #[test]
+    fn update_changes_only_given_fields() {
+        let store = MemoryStore::new();
+        store.create(Link { redirect_code: Some(RedirectCode::Found), ..Link::new("abc", "https://a.example") }).unwrap();
+        store.increment("abc", Counter::Redirects).unwrap();
+
+        let update = LinkUpdate { tags: Some(vec!["promo".to_string()]), ..LinkUpdate::default() };
+        let link = store.update("abc", update).unwrap().unwrap();
+        assert_eq!((link.url.as_str(), link.redirect_code), ("https://a.example", Some(RedirectCode::Found)));
+        assert_eq!((link.tags, link.redirects), (vec!["promo".to_string()], 1));
+
+        let update = LinkUpdate { url: Some("https://b.example".to_string()), redirect_code: Some(None), ..LinkUpdate::default() };
+        let link = store.update("abc", update).unwrap().unwrap();
+        assert_eq!((link.url.as_str(), link.redirect_code), ("https://b.example", None));
+        assert_eq!(store.update("missing", LinkUpdate::default()).unwrap(), None);
+    }
+
+    #[test]
+    fn update_tells_null_from_missing() {
+        let update: LinkUpdate = serde_json::from_str(r#"{"owner": null}"#).unwrap();
+        assert_eq!((update.owner, update.redirect_code), (Some(None), None));
+        let update: LinkUpdate = serde_json::from_str(r#"{"redirect_code": 301}"#).unwrap();
+        assert_eq!(update.redirect_code, Some(Some(RedirectCode::MovedPermanently)));
+    }
+
+    #[test]
+    fn top_orders_by_requested_counter() {
+        let store = MemoryStore::new();
+        store.create(Link::new("a", "https://a.example")).unwrap();
+        store.create(Link::new("b", "https://b.example")).unwrap();
+        store.increment("a", Counter::Redirects).unwrap();
+        store.increment("b", Counter::Creations).unwrap();
+        assert_eq!(store.increment("missing", Counter::Redirects).unwrap(), None);
+
+        let top = store.top(1, Counter::Creations, None).unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].0.key, "b");
+        assert_eq!(top[0].0.creations, 2);
+        assert_eq!(top[0].0.redirects, 0);
+
+        let top = store.top(1, Counter::Redirects, None).unwrap();
+        assert_eq!(top[0].0.key, "a");
+        assert_eq!(top[0].1, 1);
+    }
+
+    #[test]
+    fn windowed_top_only_sums_recent_buckets() {
+        let store = MemoryStore::new();
+        store.create(Link::new("old", "https://old.example")).unwrap();
+        store.create(Link::new("new", "https://new.example")).unwrap();
+        let now = 1_000 * BUCKET_SECS;
+        for _ in 0..5 {
+            store.increment_at("old", Counter::Redirects, now - 2 * 24 * 3600).unwrap();
+        }
+        store.increment_at("new", Counter::Redirects, now).unwrap();
+        store.increment_at("new", Counter::Redirects, now).unwrap();
+
+        let top = store.top(10, Counter::Redirects, Some(now - 3600)).unwrap();
+        let keys: Vec<(&str, u64)> = top.iter().map(|(link, count)| (link.key.as_str(), *count)).collect();
+        assert_eq!(keys, [("new", 2)]);
+
+        let top = store.top(10, Counter::Redirects, Some(now - 7 * 24 * 3600)).unwrap();
+        assert_eq!(top[0].0.key, "old");
+        assert_eq!(top[0].1, 5);
+
+        let top = store.top(10, Counter::Redirects, None).unwrap();
+        assert_eq!(top.len(), 2);
+    }
+
+    #[test]
+    fn old_buckets_are_dropped() {
+        let store = MemoryStore::new();
+        store.create(Link::new("a", "https://a.example")).unwrap();
+        store.increment_at("a", Counter::Redirects, 0).unwrap();
+        store.increment_at("a", Counter::Redirects, BUCKET_RETENTION_SECS + 2 * BUCKET_SECS).unwrap();
+        assert_eq!(store.buckets().len(), 1);
+        assert_eq!(store.resolve("a").unwrap().unwrap().redirects, 2);
+    }
+}
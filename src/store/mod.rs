@@ -0,0 +1,410 @@
+//! Storage backends for shortened links.
+//!
+//! Handlers never touch a concrete map directly; they go through the
+//! [`LinkStore`] trait, which is handed to them as `web::Data<dyn LinkStore>`.
+
+mod memory;
+mod sqlite;
+mod wal;
+
+pub use memory::MemoryStore;
+pub use sqlite::SqliteStore;
+pub use wal::WalStore;
+
+use actix_web::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::domains::domain_of;
+
+/// A single shortened link as kept by a store.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Link {
+    pub key: String,
+    pub url: String,
+    /// Times the link was submitted for shortening, including the first time.
+    /// Older snapshots only had a single `count`, which is read as creations.
+    #[serde(alias = "count")]
+    pub creations: u64,
+    /// Times the key was looked up through the API.
+    #[serde(default)]
+    pub resolutions: u64,
+    /// Times a browser was redirected through the key.
+    #[serde(default)]
+    pub redirects: u64,
+    /// Seconds since the Unix epoch.
+    pub created_at: u64,
+    /// Per-link override of the server-wide redirect code.
+    #[serde(default)]
+    pub redirect_code: Option<RedirectCode>,
+    /// Free-form labels, sorted and without duplicates.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Whoever the link belongs to, as given by the client.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Seconds since the Unix epoch after which the link stops redirecting.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+}
+
+impl Link {
+    pub fn new(key: &str, url: &str) -> Link {
+        Link {
+            key: key.to_string(),
+            url: url.to_string(),
+            creations: 1,
+            resolutions: 0,
+            redirects: 0,
+            created_at: unix_now(),
+            redirect_code: None,
+            tags: Vec::new(),
+            owner: None,
+            expires_at: None,
+        }
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// HTTP status used when redirecting a short key, serialized as the bare number.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "u16", into = "u16")]
+pub enum RedirectCode {
+    /// 301, cached by browsers and search engines.
+    MovedPermanently,
+    /// 302
+    Found,
+    /// 307, the default.
+    #[default]
+    TemporaryRedirect,
+    /// 308, like 301 but keeps the request method.
+    PermanentRedirect,
+}
+
+impl RedirectCode {
+    pub fn status(self) -> StatusCode {
+        match self {
+            RedirectCode::MovedPermanently => StatusCode::MOVED_PERMANENTLY,
+            RedirectCode::Found => StatusCode::FOUND,
+            RedirectCode::TemporaryRedirect => StatusCode::TEMPORARY_REDIRECT,
+            RedirectCode::PermanentRedirect => StatusCode::PERMANENT_REDIRECT,
+        }
+    }
+}
+
+impl TryFrom<u16> for RedirectCode {
+    type Error = String;
+
+    fn try_from(code: u16) -> Result<RedirectCode, String> {
+        match code {
+            301 => Ok(RedirectCode::MovedPermanently),
+            302 => Ok(RedirectCode::Found),
+            307 => Ok(RedirectCode::TemporaryRedirect),
+            308 => Ok(RedirectCode::PermanentRedirect),
+            other => Err(format!("unsupported redirect code {}, expected 301, 302, 307 or 308", other)),
+        }
+    }
+}
+
+impl From<RedirectCode> for u16 {
+    fn from(code: RedirectCode) -> u16 {
+        code.status().as_u16()
+    }
+}
+
+impl Link {
+    pub fn count(&self, counter: Counter) -> u64 {
+        match counter {
+            Counter::Creations => self.creations,
+            Counter::Resolutions => self.resolutions,
+            Counter::Redirects => self.redirects,
+        }
+    }
+
+    fn count_mut(&mut self, counter: Counter) -> &mut u64 {
+        match counter {
+            Counter::Creations => &mut self.creations,
+            Counter::Resolutions => &mut self.resolutions,
+            Counter::Redirects => &mut self.redirects,
+        }
+    }
+}
+
+/// Changes to a stored link; fields left `None` are kept as they are.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LinkUpdate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// `Some(None)` drops the link's override, so the server default applies again.
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "present")]
+    pub redirect_code: Option<Option<RedirectCode>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    /// `Some(None)` removes the owner.
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "present")]
+    pub owner: Option<Option<String>>,
+    /// `Some(None)` makes the link never expire.
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "present")]
+    pub expires_at: Option<Option<u64>>,
+}
+
+impl LinkUpdate {
+    pub fn apply(&self, link: &mut Link) {
+        if let Some(url) = &self.url {
+            link.url = url.clone();
+        }
+        if let Some(redirect_code) = self.redirect_code {
+            link.redirect_code = redirect_code;
+        }
+        if let Some(tags) = &self.tags {
+            link.tags = tags.clone();
+        }
+        if let Some(owner) = &self.owner {
+            link.owner = owner.clone();
+        }
+        if let Some(expires_at) = self.expires_at {
+            link.expires_at = expires_at;
+        }
+    }
+}
+
+/// Reads a field that is present, even as `null`, as `Some`, so an explicit
+/// `null` can be told apart from a missing field.
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// The separate things a link counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Counter {
+    /// Submissions of the URL for shortening.
+    Creations,
+    /// API lookups of the key.
+    Resolutions,
+    /// Redirects served for the key.
+    Redirects,
+}
+
+impl std::str::FromStr for Counter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Counter, String> {
+        match s {
+            "creations" => Ok(Counter::Creations),
+            "resolutions" => Ok(Counter::Resolutions),
+            "redirects" => Ok(Counter::Redirects),
+            other => Err(format!("unknown counter {}, expected creations, resolutions or redirects", other)),
+        }
+    }
+}
+
+/// Order of [`LinkStore::list_page`]; ties are broken by key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkSort {
+    /// Oldest first.
+    #[default]
+    Created,
+    /// Most redirected first.
+    Clicks,
+}
+
+/// One page of links, as asked of [`LinkStore::list_page`]. Unset filters match every link.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkQuery {
+    pub sort: LinkSort,
+    /// Sort value and key of the last link of the previous page; only links
+    /// after it are returned, so inserts and deletes do not shift pages.
+    pub after: Option<(u64, String)>,
+    pub limit: usize,
+    /// Destination host, lowercase and without a trailing dot.
+    pub domain: Option<String>,
+    pub tag: Option<String>,
+    pub owner: Option<String>,
+    /// Only links created at or after this Unix time.
+    pub since: Option<u64>,
+    /// Only links created before this Unix time.
+    pub until: Option<u64>,
+}
+
+impl LinkQuery {
+    pub fn matches(&self, link: &Link) -> bool {
+        self.domain.as_ref().is_none_or(|domain| domain_of(&link.url).as_ref() == Some(domain))
+            && self.tag.as_ref().is_none_or(|tag| link.tags.contains(tag))
+            && self.owner.as_ref().is_none_or(|owner| link.owner.as_ref() == Some(owner))
+            && self.since.is_none_or(|since| link.created_at >= since)
+            && self.until.is_none_or(|until| link.created_at < until)
+    }
+
+    /// The value `link` is sorted by.
+    pub fn sort_value(&self, link: &Link) -> u64 {
+        match self.sort {
+            LinkSort::Created => link.created_at,
+            LinkSort::Clicks => link.redirects,
+        }
+    }
+
+    fn order(&self, a: (u64, &str), b: (u64, &str)) -> Ordering {
+        let by_value = match self.sort {
+            LinkSort::Created => a.0.cmp(&b.0),
+            LinkSort::Clicks => b.0.cmp(&a.0),
+        };
+        by_value.then_with(|| a.1.cmp(b.1))
+    }
+
+    /// Filters, sorts and cuts `links` down to this page, for stores that
+    /// cannot do better than looking at every link.
+    pub fn page(&self, links: Vec<Link>) -> Vec<Link> {
+        let mut links: Vec<Link> = links
+            .into_iter()
+            .filter(|link| self.matches(link))
+            .filter(|link| {
+                self.after.as_ref().is_none_or(|(value, key)| {
+                    self.order((self.sort_value(link), &link.key), (*value, key)) == Ordering::Greater
+                })
+            })
+            .collect();
+        links.sort_by(|a, b| self.order((self.sort_value(a), &a.key), (self.sort_value(b), &b.key)));
+        links.truncate(self.limit);
+        links
+    }
+}
+
+/// How a click reached a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClickKind {
+    /// Looked up through the API.
+    Resolve,
+    /// Followed as a redirect.
+    Redirect,
+}
+
+/// One resolve or redirect of a key, as kept in the append-only event log.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ClickEvent {
+    pub key: String,
+    /// Seconds since the Unix epoch.
+    pub at: u64,
+    pub kind: ClickKind,
+    pub referrer: Option<String>,
+    pub user_agent: Option<String>,
+    /// Salted hash of the client address; the address itself is never stored.
+    pub client_ip_hash: Option<String>,
+}
+
+/// Width of the time buckets behind windowed rankings.
+pub const BUCKET_SECS: u64 = 3600;
+/// How long buckets are kept, and so the longest window that can be ranked.
+pub const BUCKET_RETENTION_SECS: u64 = 7 * 24 * 3600;
+
+/// Current time in seconds since the Unix epoch.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+#[derive(Debug)]
+pub enum StoreError {
+    /// `create` was called for a key that is already stored.
+    KeyExists(String),
+    /// The backend itself failed (I/O, database, ...).
+    Backend(String),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::KeyExists(key) => write!(f, "key already exists: {}", key),
+            StoreError::Backend(msg) => write!(f, "storage error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<std::io::Error> for StoreError {
+    fn from(err: std::io::Error) -> StoreError {
+        StoreError::Backend(err.to_string())
+    }
+}
+
+pub type StoreResult<T> = Result<T, StoreError>;
+
+/// Everything the HTTP layer needs from a link store.
+pub trait LinkStore: Send + Sync {
+    /// Inserts a new link. Fails with [`StoreError::KeyExists`] if the key is taken.
+    fn create(&self, link: Link) -> StoreResult<Link>;
+
+    /// Looks up a link by key without touching its counter.
+    fn resolve(&self, key: &str) -> StoreResult<Option<Link>>;
+
+    /// Bumps one counter of a link, lifetime and in the current time bucket,
+    /// returning its new lifetime value (`None` if unknown).
+    fn increment(&self, key: &str, counter: Counter) -> StoreResult<Option<u64>>;
+
+    /// Returns every stored link, in no particular order.
+    fn list(&self) -> StoreResult<Vec<Link>>;
+
+    /// Returns up to `query.limit` links matching `query`, in its order.
+    fn list_page(&self, query: &LinkQuery) -> StoreResult<Vec<Link>> {
+        Ok(query.page(self.list()?))
+    }
+
+    /// Number of stored links.
+    fn len(&self) -> StoreResult<usize> {
+        Ok(self.list()?.len())
+    }
+
+    fn is_empty(&self) -> StoreResult<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Changes a link's destination or metadata, returning the updated link
+    /// (`None` if unknown). Counters and the key never change.
+    fn update(&self, key: &str, update: LinkUpdate) -> StoreResult<Option<Link>>;
+
+    /// Removes a link, returning whether it existed. The key is remembered
+    /// as deleted, see [`LinkStore::deleted_at`].
+    fn delete(&self, key: &str) -> StoreResult<bool>;
+
+    /// When a link was last deleted under `key`, if ever. Kept even once the
+    /// key is in use again.
+    fn deleted_at(&self, key: &str) -> StoreResult<Option<u64>>;
+
+    /// Runs `work` against a view of the store whose writes are applied as
+    /// one unit where the backend supports it; an error from `work` then
+    /// undoes them all. Other backends run `work` against the store itself.
+    fn transaction(&self, work: &mut dyn FnMut(&dyn LinkStore) -> StoreResult<()>) -> StoreResult<()>;
+
+    /// Returns the `limit` links with the highest `counter`, highest first,
+    /// each with the count it was ranked by.
+    ///
+    /// With `since` set only counts from buckets starting at or after the
+    /// bucket containing `since` are summed, and links without any are left out;
+    /// otherwise lifetime counts are used.
+    fn top(&self, limit: usize, counter: Counter, since: Option<u64>) -> StoreResult<Vec<(Link, u64)>>;
+
+    /// Appends a click to the event log; recorded events are never changed.
+    fn record_event(&self, event: ClickEvent) -> StoreResult<()>;
+
+    /// Returns up to `limit` events for `key` recorded at or after `since`, newest first.
+    fn events(&self, key: &str, since: u64, limit: usize) -> StoreResult<Vec<ClickEvent>>;
+}
+
+/// Orders links by descending count, breaking ties by key so results are stable.
+pub(crate) fn rank_links(links: &mut [(Link, u64)]) {
+    links.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.key.cmp(&b.key)));
+}
@@ -0,0 +1,695 @@
+use std::path::Path;
+
+use parking_lot::ReentrantMutex;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, Value, ValueRef};
+use rusqlite::{params, Connection, ErrorCode, OptionalExtension, Row, ToSql, Transaction};
+
+use crate::domains::domain_of;
+use crate::metrics::timed_reentrant_lock;
+use super::{
+    unix_now, ClickEvent, ClickKind, Counter, Link, LinkQuery, LinkSort, LinkStore, LinkUpdate, RedirectCode,
+    StoreError, StoreResult, BUCKET_RETENTION_SECS, BUCKET_SECS,
+};
+
+/// Schema migrations, applied in order. The index of the last applied entry
+/// plus one is kept in `PRAGMA user_version`, so only append to this list.
+const MIGRATIONS: &[&str] = &[
+    // 1: initial links table
+    "CREATE TABLE links (
+        key        TEXT PRIMARY KEY NOT NULL,
+        url        TEXT NOT NULL,
+        clicks     INTEGER NOT NULL DEFAULT 0,
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX links_clicks ON links (clicks DESC);",
+    // 2: per-link redirect status, NULL means the server default
+    "ALTER TABLE links ADD COLUMN redirect_code INTEGER;",
+    // 3: split the single counter; what was counted so far is kept as creations
+    "ALTER TABLE links RENAME COLUMN clicks TO creations;
+    ALTER TABLE links ADD COLUMN resolutions INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE links ADD COLUMN redirects INTEGER NOT NULL DEFAULT 0;",
+    // 4: append-only click log
+    "CREATE TABLE click_events (
+        id             INTEGER PRIMARY KEY,
+        key            TEXT NOT NULL,
+        at             INTEGER NOT NULL,
+        kind           TEXT NOT NULL,
+        referrer       TEXT,
+        user_agent     TEXT,
+        client_ip_hash TEXT
+    );
+    CREATE INDEX click_events_key_at ON click_events (key, at);",
+    // 5: counts per time bucket, for windowed rankings
+    "CREATE TABLE link_buckets (
+        key     TEXT NOT NULL,
+        counter TEXT NOT NULL,
+        bucket  INTEGER NOT NULL,
+        count   INTEGER NOT NULL,
+        PRIMARY KEY (key, counter, bucket)
+    );
+    CREATE INDEX link_buckets_counter_bucket ON link_buckets (counter, bucket);",
+    // 6: link metadata; tags are a JSON array of strings
+    "ALTER TABLE links ADD COLUMN tags TEXT NOT NULL DEFAULT '[]';
+    ALTER TABLE links ADD COLUMN owner TEXT;",
+    // 7: link expiry, and when deleted keys were deleted
+    "ALTER TABLE links ADD COLUMN expires_at INTEGER;
+    CREATE TABLE deleted_links (
+        key        TEXT PRIMARY KEY NOT NULL,
+        deleted_at INTEGER NOT NULL
+    );",
+    // 8: destination host, so listings can filter by domain in SQL; filled in
+    // by `backfill_domains` since it takes Rust to work out
+    "ALTER TABLE links ADD COLUMN domain TEXT;
+    CREATE INDEX links_domain ON links (domain);",
+];
+
+/// Durable store backed by an embedded SQLite database.
+pub struct SqliteStore {
+    /// Re-entrant so a [`LinkStore::transaction`] can hold the connection for
+    /// its whole batch while the batch calls back into the store.
+    conn: ReentrantMutex<Connection>,
+}
+
+impl SqliteStore {
+    /// Opens (or creates) the database at `path` and brings its schema up to date.
+    pub fn open<P: AsRef<Path>>(path: P) -> StoreResult<SqliteStore> {
+        SqliteStore::with_connection(Connection::open(path)?)
+    }
+
+    /// Opens a throwaway database that lives only as long as the store.
+    #[cfg(test)]
+    pub fn open_in_memory() -> StoreResult<SqliteStore> {
+        SqliteStore::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(mut conn: Connection) -> StoreResult<SqliteStore> {
+        migrate(&mut conn)?;
+        Ok(SqliteStore {
+            conn: ReentrantMutex::new(conn),
+        })
+    }
+
+    /// `increment` as if it happened at `at`.
+    fn increment_at(&self, key: &str, counter: Counter, at: u64) -> StoreResult<Option<u64>> {
+        let conn = timed_reentrant_lock(&self.conn, "sqlite.conn");
+        let tx = begin(&conn)?;
+        let column = counter_column(counter);
+        let count = conn
+            .query_row(
+                &format!(
+                    "UPDATE links SET {0} = {0} + 1 WHERE key = ?1 RETURNING {0}",
+                    column
+                ),
+                [key],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if count.is_some() {
+            conn.execute(
+                "INSERT INTO link_buckets (key, counter, bucket, count) VALUES (?1, ?2, ?3, 1)
+                 ON CONFLICT DO UPDATE SET count = count + 1",
+                params![key, column, at / BUCKET_SECS],
+            )?;
+            // Drop whatever no window reaches any more.
+            conn.execute(
+                "DELETE FROM link_buckets WHERE key = ?1 AND counter = ?2 AND bucket < ?3",
+                params![key, column, at.saturating_sub(BUCKET_RETENTION_SECS) / BUCKET_SECS],
+            )?;
+        }
+        commit(tx)?;
+        Ok(count)
+    }
+}
+
+/// Starts a transaction for a multi-statement write, unless the write is
+/// already part of one opened by [`LinkStore::transaction`].
+fn begin(conn: &Connection) -> rusqlite::Result<Option<Transaction<'_>>> {
+    if conn.is_autocommit() {
+        conn.unchecked_transaction().map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+fn commit(tx: Option<Transaction<'_>>) -> rusqlite::Result<()> {
+    tx.map_or(Ok(()), Transaction::commit)
+}
+
+/// Applies every migration newer than the database's `user_version`.
+fn migrate(conn: &mut Connection) -> StoreResult<()> {
+    let applied: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (index, sql) in MIGRATIONS.iter().enumerate().skip(applied) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)?;
+        tx.pragma_update(None, "user_version", index + 1)?;
+        tx.commit()?;
+    }
+    backfill_domains(conn)
+}
+
+/// Fills in `domain` for links stored before it existed.
+fn backfill_domains(conn: &mut Connection) -> StoreResult<()> {
+    let tx = conn.transaction()?;
+    let links = {
+        let mut stmt = tx.prepare("SELECT key, url FROM links WHERE domain IS NULL")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()?
+    };
+    for (key, url) in links {
+        tx.execute("UPDATE links SET domain = ?2 WHERE key = ?1", params![key, domain_column(&url)])?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// What goes in the `domain` column: empty rather than NULL for URLs without
+/// a host, so `backfill_domains` does not revisit them.
+fn domain_column(url: &str) -> String {
+    domain_of(url).unwrap_or_default()
+}
+
+/// `value` as an SQLite integer, which is signed; larger values are an error
+/// rather than wrapping around to negative ones.
+fn sql_integer(value: u64) -> StoreResult<Value> {
+    i64::try_from(value)
+        .map(Value::Integer)
+        .map_err(|_| StoreError::Backend(format!("{} does not fit an SQLite integer", value)))
+}
+
+fn link_from_row(row: &Row<'_>) -> rusqlite::Result<Link> {
+    Ok(Link {
+        key: row.get("key")?,
+        url: row.get("url")?,
+        creations: row.get("creations")?,
+        resolutions: row.get("resolutions")?,
+        redirects: row.get("redirects")?,
+        created_at: row.get("created_at")?,
+        redirect_code: row.get("redirect_code")?,
+        tags: tags_from_json(&row.get::<_, String>("tags")?)?,
+        owner: row.get("owner")?,
+        expires_at: row.get("expires_at")?,
+    })
+}
+
+fn tags_to_json(tags: &[String]) -> String {
+    serde_json::to_string(tags).expect("a list of strings always serializes")
+}
+
+fn tags_from_json(json: &str) -> rusqlite::Result<Vec<String>> {
+    serde_json::from_str(json).map_err(|err| FromSqlError::Other(Box::new(err)).into())
+}
+
+fn event_from_row(row: &Row<'_>) -> rusqlite::Result<ClickEvent> {
+    Ok(ClickEvent {
+        key: row.get("key")?,
+        at: row.get("at")?,
+        kind: row.get("kind")?,
+        referrer: row.get("referrer")?,
+        user_agent: row.get("user_agent")?,
+        client_ip_hash: row.get("client_ip_hash")?,
+    })
+}
+
+fn counter_column(counter: Counter) -> &'static str {
+    match counter {
+        Counter::Creations => "creations",
+        Counter::Resolutions => "resolutions",
+        Counter::Redirects => "redirects",
+    }
+}
+
+impl ToSql for RedirectCode {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(u16::from(*self)))
+    }
+}
+
+impl FromSql for RedirectCode {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<RedirectCode> {
+        let code = u16::column_result(value)?;
+        RedirectCode::try_from(code).map_err(|_| FromSqlError::OutOfRange(code.into()))
+    }
+}
+
+impl ToSql for ClickKind {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(match self {
+            ClickKind::Resolve => "resolve",
+            ClickKind::Redirect => "redirect",
+        }))
+    }
+}
+
+impl FromSql for ClickKind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<ClickKind> {
+        match value.as_str()? {
+            "resolve" => Ok(ClickKind::Resolve),
+            "redirect" => Ok(ClickKind::Redirect),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(err: rusqlite::Error) -> StoreError {
+        StoreError::Backend(err.to_string())
+    }
+}
+
+impl LinkStore for SqliteStore {
+    fn create(&self, link: Link) -> StoreResult<Link> {
+        let conn = timed_reentrant_lock(&self.conn, "sqlite.conn");
+        let inserted = conn.execute(
+            "INSERT INTO links (key, url, creations, resolutions, redirects, created_at, redirect_code, tags, owner, expires_at, domain)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                link.key,
+                link.url,
+                link.creations,
+                link.resolutions,
+                link.redirects,
+                link.created_at,
+                link.redirect_code,
+                tags_to_json(&link.tags),
+                link.owner,
+                link.expires_at,
+                domain_column(&link.url)
+            ],
+        );
+        match inserted {
+            Ok(_) => Ok(link),
+            Err(rusqlite::Error::SqliteFailure(err, _))
+                if err.code == ErrorCode::ConstraintViolation =>
+            {
+                Err(StoreError::KeyExists(link.key))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn resolve(&self, key: &str) -> StoreResult<Option<Link>> {
+        let conn = timed_reentrant_lock(&self.conn, "sqlite.conn");
+        let link = conn
+            .query_row("SELECT * FROM links WHERE key = ?1", [key], link_from_row)
+            .optional()?;
+        Ok(link)
+    }
+
+    fn increment(&self, key: &str, counter: Counter) -> StoreResult<Option<u64>> {
+        self.increment_at(key, counter, unix_now())
+    }
+
+    fn list(&self) -> StoreResult<Vec<Link>> {
+        let conn = timed_reentrant_lock(&self.conn, "sqlite.conn");
+        let mut stmt = conn.prepare("SELECT * FROM links")?;
+        let links = stmt
+            .query_map([], link_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(links)
+    }
+
+    fn list_page(&self, query: &LinkQuery) -> StoreResult<Vec<Link>> {
+        let (column, direction, past) = match query.sort {
+            LinkSort::Created => ("created_at", "ASC", ">"),
+            LinkSort::Clicks => ("redirects", "DESC", "<"),
+        };
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        let mut condition = |sql: String, bound: Vec<Value>| {
+            conditions.push(sql);
+            values.extend(bound);
+        };
+        if let Some(domain) = &query.domain {
+            condition("domain = ?".to_string(), vec![Value::Text(domain.clone())]);
+        }
+        if let Some(tag) = &query.tag {
+            condition(
+                "EXISTS (SELECT 1 FROM json_each(links.tags) WHERE json_each.value = ?)".to_string(),
+                vec![Value::Text(tag.clone())],
+            );
+        }
+        if let Some(owner) = &query.owner {
+            condition("owner = ?".to_string(), vec![Value::Text(owner.clone())]);
+        }
+        if let Some(since) = query.since {
+            condition("created_at >= ?".to_string(), vec![sql_integer(since)?]);
+        }
+        if let Some(until) = query.until {
+            condition("created_at < ?".to_string(), vec![sql_integer(until)?]);
+        }
+        if let Some((value, key)) = &query.after {
+            let value = sql_integer(*value)?;
+            condition(
+                format!("({0} {1} ? OR ({0} = ? AND key > ?))", column, past),
+                vec![value.clone(), value, Value::Text(key.clone())],
+            );
+        }
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        values.push(Value::Integer(query.limit.min(i64::MAX as usize) as i64));
+
+        let conn = timed_reentrant_lock(&self.conn, "sqlite.conn");
+        let mut stmt = conn.prepare(&format!(
+            "SELECT * FROM links {} ORDER BY {} {}, key ASC LIMIT ?",
+            filter, column, direction
+        ))?;
+        let links = stmt
+            .query_map(rusqlite::params_from_iter(values), link_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(links)
+    }
+
+    fn len(&self) -> StoreResult<usize> {
+        let conn = timed_reentrant_lock(&self.conn, "sqlite.conn");
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM links", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    fn update(&self, key: &str, update: LinkUpdate) -> StoreResult<Option<Link>> {
+        let conn = timed_reentrant_lock(&self.conn, "sqlite.conn");
+        let tx = begin(&conn)?;
+        let link = conn
+            .query_row("SELECT * FROM links WHERE key = ?1", [key], link_from_row)
+            .optional()?;
+        let Some(mut link) = link else {
+            return Ok(None);
+        };
+        update.apply(&mut link);
+        conn.execute(
+            "UPDATE links SET url = ?2, redirect_code = ?3, tags = ?4, owner = ?5, expires_at = ?6, domain = ?7 WHERE key = ?1",
+            params![
+                key,
+                link.url,
+                link.redirect_code,
+                tags_to_json(&link.tags),
+                link.owner,
+                link.expires_at,
+                domain_column(&link.url)
+            ],
+        )?;
+        commit(tx)?;
+        Ok(Some(link))
+    }
+
+    fn delete(&self, key: &str) -> StoreResult<bool> {
+        let conn = timed_reentrant_lock(&self.conn, "sqlite.conn");
+        let tx = begin(&conn)?;
+        conn.execute("DELETE FROM link_buckets WHERE key = ?1", [key])?;
+        let deleted = conn.execute("DELETE FROM links WHERE key = ?1", [key])? > 0;
+        if deleted {
+            conn.execute(
+                "INSERT INTO deleted_links (key, deleted_at) VALUES (?1, ?2)
+                 ON CONFLICT DO UPDATE SET deleted_at = excluded.deleted_at",
+                params![key, unix_now()],
+            )?;
+        }
+        commit(tx)?;
+        Ok(deleted)
+    }
+
+    fn deleted_at(&self, key: &str) -> StoreResult<Option<u64>> {
+        let conn = timed_reentrant_lock(&self.conn, "sqlite.conn");
+        let deleted_at = conn
+            .query_row("SELECT deleted_at FROM deleted_links WHERE key = ?1", [key], |row| row.get(0))
+            .optional()?;
+        Ok(deleted_at)
+    }
+
+    fn top(&self, limit: usize, counter: Counter, since: Option<u64>) -> StoreResult<Vec<(Link, u64)>> {
+        let conn = timed_reentrant_lock(&self.conn, "sqlite.conn");
+        let column = counter_column(counter);
+        let ranked_row = |row: &Row<'_>| Ok((link_from_row(row)?, row.get("ranked_count")?));
+        let ranked = match since {
+            None => {
+                let mut stmt = conn.prepare(&format!(
+                    "SELECT *, {0} AS ranked_count FROM links ORDER BY {0} DESC, key ASC LIMIT ?1",
+                    column
+                ))?;
+                let ranked = stmt
+                    .query_map(params![limit as i64], ranked_row)?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                ranked
+            }
+            Some(since) => {
+                let mut stmt = conn.prepare(
+                    "SELECT links.*, SUM(link_buckets.count) AS ranked_count
+                     FROM link_buckets JOIN links ON links.key = link_buckets.key
+                     WHERE link_buckets.counter = ?1 AND link_buckets.bucket >= ?2
+                     GROUP BY links.key
+                     ORDER BY ranked_count DESC, links.key ASC
+                     LIMIT ?3",
+                )?;
+                let ranked = stmt
+                    .query_map(params![column, since / BUCKET_SECS, limit as i64], ranked_row)?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                ranked
+            }
+        };
+        Ok(ranked)
+    }
+
+    fn record_event(&self, event: ClickEvent) -> StoreResult<()> {
+        let conn = timed_reentrant_lock(&self.conn, "sqlite.conn");
+        conn.execute(
+            "INSERT INTO click_events (key, at, kind, referrer, user_agent, client_ip_hash)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                event.key,
+                event.at,
+                event.kind,
+                event.referrer,
+                event.user_agent,
+                event.client_ip_hash
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn events(&self, key: &str, since: u64, limit: usize) -> StoreResult<Vec<ClickEvent>> {
+        let conn = timed_reentrant_lock(&self.conn, "sqlite.conn");
+        let mut stmt = conn.prepare(
+            "SELECT * FROM click_events WHERE key = ?1 AND at >= ?2 ORDER BY at DESC, id DESC LIMIT ?3",
+        )?;
+        let events = stmt
+            .query_map(params![key, since, limit as i64], event_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(events)
+    }
+
+    /// Runs `work` against this store inside one SQLite transaction on its
+    /// connection. The lock is held for the whole batch, so other threads wait
+    /// instead of slipping their writes into it, while `work` re-enters the
+    /// lock on this thread and joins the open transaction through `begin`.
+    /// Dropping `tx` on an error or a panic rolls the batch back.
+    fn transaction(&self, work: &mut dyn FnMut(&dyn LinkStore) -> StoreResult<()>) -> StoreResult<()> {
+        let conn = timed_reentrant_lock(&self.conn, "sqlite.conn");
+        let tx = begin(&conn)?;
+        work(self)?;
+        commit(tx)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_survive_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("links.db");
+
+        let store = SqliteStore::open(&path).unwrap();
+        store.create(Link::new("abc", "https://a.example")).unwrap();
+        store.increment("abc", Counter::Creations).unwrap();
+        store.increment("abc", Counter::Resolutions).unwrap();
+        drop(store);
+
+        let store = SqliteStore::open(&path).unwrap();
+        let link = store.resolve("abc").unwrap().unwrap();
+        assert_eq!(link.url, "https://a.example");
+        assert_eq!((link.creations, link.resolutions, link.redirects), (2, 1, 0));
+        assert_eq!(link.redirect_code, None);
+    }
+
+    #[test]
+    fn updates_survive_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("links.db");
+
+        let store = SqliteStore::open(&path).unwrap();
+        store.create(Link { tags: vec!["a".to_string()], ..Link::new("abc", "https://a.example") }).unwrap();
+        let update = LinkUpdate {
+            url: Some("https://b.example".to_string()),
+            tags: Some(vec!["b".to_string(), "c".to_string()]),
+            owner: Some(Some("ops".to_string())),
+            expires_at: Some(Some(1_900_000_000)),
+            ..LinkUpdate::default()
+        };
+        store.update("abc", update).unwrap().unwrap();
+        store.create(Link::new("def", "https://d.example")).unwrap();
+        assert!(store.delete("def").unwrap());
+        assert_eq!(store.update("missing", LinkUpdate::default()).unwrap(), None);
+        drop(store);
+
+        let store = SqliteStore::open(&path).unwrap();
+        let link = store.resolve("abc").unwrap().unwrap();
+        assert_eq!(link.url, "https://b.example");
+        assert_eq!(link.expires_at, Some(1_900_000_000));
+        assert!(store.deleted_at("def").unwrap().is_some());
+        assert_eq!(store.deleted_at("abc").unwrap(), None);
+        assert_eq!(link.tags, ["b", "c"]);
+        assert_eq!(link.owner.as_deref(), Some("ops"));
+    }
+
+    #[test]
+    fn transaction_commits_or_rolls_back_as_a_whole() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        store
+            .transaction(&mut |tx| {
+                tx.create(Link::new("abc", "https://a.example"))?;
+                tx.increment("abc", Counter::Creations)?;
+                assert!(matches!(tx.create(Link::new("abc", "https://b.example")), Err(StoreError::KeyExists(_))));
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(store.resolve("abc").unwrap().unwrap().creations, 2);
+
+        let failed = store.transaction(&mut |tx| {
+            tx.create(Link::new("def", "https://d.example"))?;
+            tx.delete("abc")?;
+            Err(StoreError::Backend("disk full".to_string()))
+        });
+        assert!(failed.is_err());
+        assert!(store.resolve("abc").unwrap().is_some());
+        assert!(store.resolve("def").unwrap().is_none());
+    }
+
+    #[test]
+    fn panicking_transaction_rolls_back_and_keeps_the_store_usable() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            store.transaction(&mut |tx| {
+                tx.create(Link::new("abc", "https://a.example"))?;
+                panic!("batch gave up");
+            })
+        }));
+        assert!(panicked.is_err());
+        assert!(store.resolve("abc").unwrap().is_none());
+        store.create(Link::new("abc", "https://a.example")).unwrap();
+        assert!(store.resolve("abc").unwrap().is_some());
+    }
+
+    #[test]
+    fn migrates_single_counter_to_creations() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(MIGRATIONS[0]).unwrap();
+        conn.execute_batch(MIGRATIONS[1]).unwrap();
+        conn.pragma_update(None, "user_version", 2).unwrap();
+        conn.execute(
+            "INSERT INTO links (key, url, clicks, created_at) VALUES ('abc', 'https://a.example', 7, 0)",
+            [],
+        )
+        .unwrap();
+
+        let store = SqliteStore::with_connection(conn).unwrap();
+        let link = store.resolve("abc").unwrap().unwrap();
+        assert_eq!((link.creations, link.resolutions, link.redirects), (7, 0, 0));
+    }
+
+    #[test]
+    fn backfills_domains_for_listing() {
+        let conn = Connection::open_in_memory().unwrap();
+        for sql in &MIGRATIONS[..8] {
+            conn.execute_batch(sql).unwrap();
+        }
+        conn.pragma_update(None, "user_version", 8).unwrap();
+        conn.execute(
+            "INSERT INTO links (key, url, created_at) VALUES ('a', 'https://Docs.Example.com/x', 0), ('b', 'mailto:x', 1)",
+            [],
+        )
+        .unwrap();
+
+        let store = SqliteStore::with_connection(conn).unwrap();
+        store.create(Link::new("c", "https://docs.example.com/y")).unwrap();
+        let listed = |domain: &str| {
+            let query = LinkQuery { domain: Some(domain.to_string()), limit: 10, ..LinkQuery::default() };
+            let links = store.list_page(&query).unwrap();
+            links.into_iter().map(|link| link.key).collect::<Vec<_>>()
+        };
+        assert_eq!(listed("docs.example.com"), ["a", "c"]);
+        assert_eq!(listed(""), ["b"]);
+    }
+
+    #[test]
+    fn windowed_top_only_sums_recent_buckets() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        store.create(Link::new("old", "https://old.example")).unwrap();
+        store.create(Link::new("new", "https://new.example")).unwrap();
+        let now = 1_000 * BUCKET_SECS;
+        for _ in 0..5 {
+            store.increment_at("old", Counter::Redirects, now - 2 * 24 * 3600).unwrap();
+        }
+        store.increment_at("new", Counter::Redirects, now).unwrap();
+        store.increment_at("new", Counter::Resolutions, now).unwrap();
+
+        let top = store.top(10, Counter::Redirects, Some(now - 3600)).unwrap();
+        let keys: Vec<(&str, u64)> = top.iter().map(|(link, count)| (link.key.as_str(), *count)).collect();
+        assert_eq!(keys, [("new", 1)]);
+
+        let top = store.top(10, Counter::Redirects, Some(now - 7 * 24 * 3600)).unwrap();
+        assert_eq!((top[0].0.key.as_str(), top[0].1), ("old", 5));
+
+        let top = store.top(1, Counter::Redirects, None).unwrap();
+        assert_eq!((top[0].0.key.as_str(), top[0].1), ("old", 5));
+    }
+
+    #[test]
+    fn stores_click_events() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        for (at, kind) in [(10, ClickKind::Resolve), (20, ClickKind::Redirect)] {
+            store
+                .record_event(ClickEvent {
+                    key: "abc".to_string(),
+                    at,
+                    kind,
+                    referrer: None,
+                    user_agent: Some("curl/8.0".to_string()),
+                    client_ip_hash: Some("0f0f".to_string()),
+                })
+                .unwrap();
+        }
+
+        let events = store.events("abc", 0, 10).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, ClickKind::Redirect);
+        assert_eq!(events[1].user_agent.as_deref(), Some("curl/8.0"));
+        assert_eq!(store.events("abc", 15, 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn stores_redirect_code() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let link = Link {
+            redirect_code: Some(RedirectCode::MovedPermanently),
+            ..Link::new("abc", "https://a.example")
+        };
+        store.create(link).unwrap();
+        let link = store.resolve("abc").unwrap().unwrap();
+        assert_eq!(link.redirect_code, Some(RedirectCode::MovedPermanently));
+    }
+
+    #[test]
+    fn create_rejects_existing_key() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        store.create(Link::new("abc", "https://a.example")).unwrap();
+        let err = store.create(Link::new("abc", "https://b.example")).unwrap_err();
+        assert!(matches!(err, StoreError::KeyExists(_)));
+        assert_eq!(store.top(10, Counter::Redirects, None).unwrap().len(), 1);
+        assert!(store.delete("abc").unwrap());
+        assert_eq!(store.resolve("abc").unwrap(), None);
+    }
+}
@@ -0,0 +1,496 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::metrics::timed_lock;
+use super::memory::Bucket;
+use super::{unix_now, ClickEvent, Counter, Link, LinkStore, LinkUpdate, MemoryStore, StoreError, StoreResult};
+
+const LOG_FILE: &str = "links.log";
+const SNAPSHOT_FILE: &str = "links.snapshot";
+/// Click events go to their own file, which is only ever appended to.
+const EVENTS_FILE: &str = "events.log";
+
+/// One mutation, as written to the log.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Record {
+    Create { link: Link },
+    Increment {
+        key: String,
+        #[serde(default = "legacy_counter")]
+        counter: Counter,
+        /// When it happened, so replay rebuilds the time buckets. Older logs lack it.
+        #[serde(default)]
+        at: u64,
+    },
+    Update { key: String, update: LinkUpdate },
+    Delete {
+        key: String,
+        /// When it happened, for the tombstone. Older logs lack it.
+        #[serde(default)]
+        at: u64,
+    },
+}
+
+/// Logs written before counters were split have one mixed count, kept as creations.
+fn legacy_counter() -> Counter {
+    Counter::Creations
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Entry {
+    seq: u64,
+    #[serde(flatten)]
+    record: Record,
+}
+
+/// Full copy of the map plus the sequence number of the last log entry it covers.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Snapshot {
+    seq: u64,
+    links: Vec<Link>,
+    #[serde(default)]
+    buckets: Vec<Bucket>,
+    /// Deleted keys and when they were deleted.
+    #[serde(default)]
+    tombstones: HashMap<String, u64>,
+}
+
+struct Log {
+    file: File,
+    seq: u64,
+    since_snapshot: usize,
+}
+
+/// In-memory map whose mutations are appended to a log before being applied,
+/// with the log periodically compacted into a snapshot.
+///
+/// On open the snapshot is loaded and every later log entry replayed, so
+/// every link and its count survive a crash.
+pub struct WalStore {
+    links: MemoryStore,
+    log: Mutex<Log>,
+    events: Mutex<File>,
+    dir: PathBuf,
+    snapshot_every: usize,
+}
+
+impl WalStore {
+    /// Opens the log in `dir`, replaying any existing snapshot and log.
+    /// A snapshot is taken after every `snapshot_every` logged mutations.
+    pub fn open<P: AsRef<Path>>(dir: P, snapshot_every: usize) -> StoreResult<WalStore> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let links = MemoryStore::new();
+        let snapshot = read_snapshot(&dir.join(SNAPSHOT_FILE))?;
+        let mut seq = snapshot.seq;
+        for link in snapshot.links {
+            links.create(link)?;
+        }
+        for bucket in snapshot.buckets {
+            links.restore_bucket(bucket);
+        }
+        links.restore_tombstones(snapshot.tombstones);
+
+        let mut since_snapshot = 0;
+        let log_path = dir.join(LOG_FILE);
+        if log_path.exists() {
+            for entry in read_lines::<Entry>(&log_path)? {
+                if entry.seq <= seq {
+                    // Already folded into the snapshot.
+                    continue;
+                }
+                apply(&links, entry.record)?;
+                seq = entry.seq;
+                since_snapshot += 1;
+            }
+        }
+
+        let events_path = dir.join(EVENTS_FILE);
+        if events_path.exists() {
+            for event in read_lines::<ClickEvent>(&events_path)? {
+                links.record_event(event)?;
+            }
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&log_path)?;
+        let events = OpenOptions::new().create(true).append(true).open(&events_path)?;
+        Ok(WalStore {
+            links,
+            log: Mutex::new(Log { file, seq, since_snapshot }),
+            events: Mutex::new(events),
+            dir,
+            snapshot_every: snapshot_every.max(1),
+        })
+    }
+
+    /// Writes a snapshot of the current map and truncates the log.
+    fn write_snapshot(&self, log: &mut Log) -> StoreResult<()> {
+        let snapshot = Snapshot {
+            seq: log.seq,
+            links: self.links.list()?,
+            buckets: self.links.buckets(),
+            tombstones: self.links.tombstones(),
+        };
+        let tmp_path = self.dir.join(format!("{}.tmp", SNAPSHOT_FILE));
+        let mut tmp = File::create(&tmp_path)?;
+        serde_json::to_writer(&mut tmp, &snapshot).map_err(io::Error::from)?;
+        tmp.sync_all()?;
+        fs::rename(&tmp_path, self.dir.join(SNAPSHOT_FILE))?;
+
+        // Entries up to `seq` are in the snapshot now; replay would skip them anyway.
+        log.file.set_len(0)?;
+        log.file.sync_all()?;
+        log.since_snapshot = 0;
+        Ok(())
+    }
+
+    /// Appends `record` to the log, then applies it to the map.
+    fn append(&self, log: &mut Log, record: Record) -> StoreResult<()> {
+        let entry = Entry {
+            seq: log.seq + 1,
+            record,
+        };
+        let mut line = serde_json::to_vec(&entry).map_err(io::Error::from)?;
+        line.push(b'\n');
+        log.file.write_all(&line)?;
+        log.seq = entry.seq;
+
+        apply(&self.links, entry.record)?;
+
+        log.since_snapshot += 1;
+        if log.since_snapshot >= self.snapshot_every {
+            self.write_snapshot(log)?;
+        }
+        Ok(())
+    }
+}
+
+fn apply(links: &MemoryStore, record: Record) -> StoreResult<()> {
+    match record {
+        Record::Create { link } => {
+            links.create(link)?;
+        }
+        Record::Increment { key, counter, at } => {
+            links.increment_at(&key, counter, at)?;
+        }
+        Record::Update { key, update } => {
+            links.update(&key, update)?;
+        }
+        Record::Delete { key, at } => {
+            links.delete_at(&key, at)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_snapshot(path: &Path) -> StoreResult<Snapshot> {
+    match File::open(path) {
+        Ok(file) => {
+            let snapshot = serde_json::from_reader(BufReader::new(file)).map_err(io::Error::from)?;
+            Ok(snapshot)
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Snapshot::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Reads one JSON value per line, stopping at the first line that does not parse.
+///
+/// Anything after the last good line is cut off the file, so that entries
+/// appended from now on are neither glued onto a torn line nor written after
+/// it, where the next replay would never reach them.
+fn read_lines<T: serde::de::DeserializeOwned>(path: &Path) -> StoreResult<Vec<T>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+    let mut good_len = 0;
+    let mut missing_newline = false;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            break;
+        }
+        match serde_json::from_slice(line.strip_suffix(b"\n").unwrap_or(&line)) {
+            Ok(entry) => {
+                entries.push(entry);
+                good_len += read as u64;
+                missing_newline = !line.ends_with(b"\n");
+            }
+            Err(err) => {
+                // A crash mid-write can leave a torn final line; nothing after it is trustworthy.
+                tracing::warn!(path = %path.display(), error = %err, "stopping replay at unreadable entry");
+                break;
+            }
+        }
+    }
+
+    if good_len < fs::metadata(path)?.len() || missing_newline {
+        let mut file = OpenOptions::new().append(true).open(path)?;
+        file.set_len(good_len)?;
+        if missing_newline {
+            file.write_all(b"\n")?;
+        }
+        file.sync_all()?;
+    }
+    Ok(entries)
+}
+
+impl LinkStore for WalStore {
+    fn create(&self, link: Link) -> StoreResult<Link> {
+        let mut log = timed_lock(&self.log, "wal.log");
+        if self.links.resolve(&link.key)?.is_some() {
+            return Err(StoreError::KeyExists(link.key));
+        }
+        self.append(&mut log, Record::Create { link: link.clone() })?;
+        Ok(link)
+    }
+
+    fn resolve(&self, key: &str) -> StoreResult<Option<Link>> {
+        self.links.resolve(key)
+    }
+
+    fn increment(&self, key: &str, counter: Counter) -> StoreResult<Option<u64>> {
+        let mut log = timed_lock(&self.log, "wal.log");
+        if self.links.resolve(key)?.is_none() {
+            return Ok(None);
+        }
+        let record = Record::Increment {
+            key: key.to_string(),
+            counter,
+            at: unix_now(),
+        };
+        self.append(&mut log, record)?;
+        Ok(self.links.resolve(key)?.map(|link| link.count(counter)))
+    }
+
+    fn list(&self) -> StoreResult<Vec<Link>> {
+        self.links.list()
+    }
+
+    fn update(&self, key: &str, update: LinkUpdate) -> StoreResult<Option<Link>> {
+        let mut log = timed_lock(&self.log, "wal.log");
+        if self.links.resolve(key)?.is_none() {
+            return Ok(None);
+        }
+        self.append(&mut log, Record::Update { key: key.to_string(), update })?;
+        self.links.resolve(key)
+    }
+
+    fn delete(&self, key: &str) -> StoreResult<bool> {
+        let mut log = timed_lock(&self.log, "wal.log");
+        if self.links.resolve(key)?.is_none() {
+            return Ok(false);
+        }
+        self.append(&mut log, Record::Delete { key: key.to_string(), at: unix_now() })?;
+        Ok(true)
+    }
+
+    fn deleted_at(&self, key: &str) -> StoreResult<Option<u64>> {
+        self.links.deleted_at(key)
+    }
+
+    fn transaction(&self, work: &mut dyn FnMut(&dyn LinkStore) -> StoreResult<()>) -> StoreResult<()> {
+        work(self)
+    }
+
+    fn top(&self, limit: usize, counter: Counter, since: Option<u64>) -> StoreResult<Vec<(Link, u64)>> {
+        self.links.top(limit, counter, since)
+    }
+
+    fn record_event(&self, event: ClickEvent) -> StoreResult<()> {
+        let mut events = timed_lock(&self.events, "wal.events");
+        let mut line = serde_json::to_vec(&event).map_err(io::Error::from)?;
+        line.push(b'\n');
+        events.write_all(&line)?;
+        self.links.record_event(event)
+    }
+
+    fn events(&self, key: &str, since: u64, limit: usize) -> StoreResult<Vec<ClickEvent>> {
+        self.links.events(key, since, limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replays_log_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let store = WalStore::open(dir.path(), 100).unwrap();
+        store.create(Link::new("a", "https://a.example")).unwrap();
+        store.create(Link::new("b", "https://b.example")).unwrap();
+        store.increment("a", Counter::Creations).unwrap();
+        store.increment("a", Counter::Redirects).unwrap();
+        store.delete("b").unwrap();
+        drop(store);
+
+        let store = WalStore::open(dir.path(), 100).unwrap();
+        let link = store.resolve("a").unwrap().unwrap();
+        assert_eq!((link.creations, link.resolutions, link.redirects), (2, 0, 1));
+        assert_eq!(store.resolve("b").unwrap(), None);
+    }
+
+    #[test]
+    fn replays_updates() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let store = WalStore::open(dir.path(), 100).unwrap();
+        store.create(Link::new("a", "https://a.example")).unwrap();
+        let update = LinkUpdate { owner: Some(Some("ops".to_string())), ..LinkUpdate::default() };
+        store.update("a", update).unwrap();
+        store.update("a", LinkUpdate { owner: Some(None), url: Some("https://b.example".to_string()), ..LinkUpdate::default() }).unwrap();
+        drop(store);
+
+        let store = WalStore::open(dir.path(), 100).unwrap();
+        let link = store.resolve("a").unwrap().unwrap();
+        assert_eq!((link.url.as_str(), link.owner), ("https://b.example", None));
+    }
+
+    #[test]
+    fn remembers_deletes_across_snapshots() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let store = WalStore::open(dir.path(), 2).unwrap();
+        store.create(Link::new("a", "https://a.example")).unwrap();
+        store.delete("a").unwrap(); // second entry triggers a snapshot
+        store.create(Link::new("b", "https://b.example")).unwrap();
+        store.delete("b").unwrap();
+        store.create(Link::new("c", "https://c.example")).unwrap();
+        store.delete("c").unwrap();
+        store.create(Link::new("c", "https://c.example/again")).unwrap();
+        let deleted_at = store.deleted_at("a").unwrap();
+        drop(store);
+
+        let store = WalStore::open(dir.path(), 2).unwrap();
+        assert!(deleted_at.is_some());
+        assert_eq!(store.deleted_at("a").unwrap(), deleted_at);
+        assert!(store.deleted_at("b").unwrap().is_some());
+        assert!(store.deleted_at("c").unwrap().is_some());
+        assert!(store.resolve("c").unwrap().is_some());
+        assert_eq!(store.deleted_at("d").unwrap(), None);
+    }
+
+    #[test]
+    fn snapshot_compacts_log() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let store = WalStore::open(dir.path(), 3).unwrap();
+        store.create(Link::new("a", "https://a.example")).unwrap();
+        store.increment("a", Counter::Redirects).unwrap();
+        store.increment("a", Counter::Redirects).unwrap(); // third entry triggers a snapshot
+        store.increment("a", Counter::Redirects).unwrap();
+        drop(store);
+
+        let log = fs::read_to_string(dir.path().join(LOG_FILE)).unwrap();
+        assert_eq!(log.lines().count(), 1);
+
+        let store = WalStore::open(dir.path(), 3).unwrap();
+        assert_eq!(store.resolve("a").unwrap().unwrap().redirects, 3);
+        // Buckets come back from the snapshot and the log alike.
+        let top = store.top(1, Counter::Redirects, Some(unix_now() - 3600)).unwrap();
+        assert_eq!(top[0].1, 3);
+    }
+
+    #[test]
+    fn ignores_torn_final_line() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let store = WalStore::open(dir.path(), 100).unwrap();
+        store.create(Link::new("a", "https://a.example")).unwrap();
+        drop(store);
+        let mut file = OpenOptions::new().append(true).open(dir.path().join(LOG_FILE)).unwrap();
+        file.write_all(b"{\"seq\":2,\"op\":\"incr").unwrap();
+
+        let mut file = OpenOptions::new().append(true).open(dir.path().join(EVENTS_FILE)).unwrap();
+        file.write_all(b"{\"key\":\"a\",\"at\":").unwrap();
+
+        let store = WalStore::open(dir.path(), 100).unwrap();
+        assert_eq!(store.resolve("a").unwrap().unwrap().creations, 1);
+        // What is written after the torn line must survive the next restart.
+        store.create(Link::new("b", "https://b.example")).unwrap();
+        store.increment("a", Counter::Redirects).unwrap();
+        store
+            .record_event(ClickEvent {
+                key: "a".to_string(),
+                at: 7,
+                kind: crate::store::ClickKind::Redirect,
+                referrer: None,
+                user_agent: None,
+                client_ip_hash: None,
+            })
+            .unwrap();
+        drop(store);
+
+        let store = WalStore::open(dir.path(), 100).unwrap();
+        assert!(store.resolve("b").unwrap().is_some());
+        assert_eq!(store.resolve("a").unwrap().unwrap().redirects, 1);
+        assert_eq!(store.events("a", 0, 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn completes_a_good_final_line_missing_its_newline() {
+        let dir = tempfile::tempdir().unwrap();
+        let entry = Entry { seq: 1, record: Record::Create { link: Link::new("a", "https://a.example") } };
+        fs::write(dir.path().join(LOG_FILE), serde_json::to_vec(&entry).unwrap()).unwrap();
+
+        let store = WalStore::open(dir.path(), 100).unwrap();
+        store.create(Link::new("b", "https://b.example")).unwrap();
+        drop(store);
+
+        let store = WalStore::open(dir.path(), 100).unwrap();
+        assert!(store.resolve("a").unwrap().is_some());
+        assert!(store.resolve("b").unwrap().is_some());
+    }
+
+    #[test]
+    fn keeps_events_across_restart_and_snapshots() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let store = WalStore::open(dir.path(), 1).unwrap();
+        store.create(Link::new("a", "https://a.example")).unwrap();
+        store
+            .record_event(ClickEvent {
+                key: "a".to_string(),
+                at: 7,
+                kind: crate::store::ClickKind::Resolve,
+                referrer: Some("https://news.example".to_string()),
+                user_agent: None,
+                client_ip_hash: None,
+            })
+            .unwrap();
+        drop(store);
+
+        let store = WalStore::open(dir.path(), 1).unwrap();
+        let events = store.events("a", 0, 10).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].referrer.as_deref(), Some("https://news.example"));
+    }
+
+    #[test]
+    fn replays_logs_from_before_split_counters() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(LOG_FILE),
+            concat!(
+                r#"{"seq":1,"op":"create","link":{"key":"a","url":"https://a.example","count":4,"created_at":0}}"#,
+                "\n",
+                r#"{"seq":2,"op":"increment","key":"a"}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+
+        let store = WalStore::open(dir.path(), 100).unwrap();
+        assert_eq!(store.resolve("a").unwrap().unwrap().creations, 5);
+    }
+}
@@ -0,0 +1,130 @@
+//! Checks applied to destination URLs and link metadata before anything is stored.
+
+use url::Url;
+
+use crate::error::ApiError;
+use crate::settings::Settings;
+
+/// Parses `raw` and checks it against the configured length limit and scheme list.
+///
+/// Returns `raw` without surrounding whitespace, which is what callers store
+/// and redirect to. Whitespace and control characters inside it are rejected
+/// rather than encoded, since they cannot go in a `Location` header as sent.
+pub fn validate_url<'a>(raw: &'a str, settings: &Settings) -> Result<&'a str, ApiError> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return Err(ApiError::EmptyUrl);
+    }
+    if raw.len() > settings.max_url_length {
+        return Err(ApiError::UrlTooLong(settings.max_url_length));
+    }
+    if raw.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err(ApiError::InvalidUrl("contains whitespace or control characters".to_string()));
+    }
+
+    let url = Url::parse(raw).map_err(|err| ApiError::InvalidUrl(err.to_string()))?;
+    if !settings.allowed_schemes.iter().any(|scheme| scheme == url.scheme()) {
+        return Err(ApiError::UnsupportedScheme(url.scheme().to_string()));
+    }
+    match url.host_str() {
+        Some(host) if !host.is_empty() => Ok(raw),
+        _ => Err(ApiError::InvalidUrl("missing host".to_string())),
+    }
+}
+
+const MAX_TAGS: usize = 20;
+const MAX_TAG_LEN: usize = 32;
+const MAX_OWNER_LEN: usize = 128;
+
+/// Checks each tag (1 to 32 of ASCII letters, digits, `-`, `_`, `.` and `:`)
+/// and returns them sorted without duplicates.
+pub fn validate_tags(tags: &[String]) -> Result<Vec<String>, ApiError> {
+    let mut tags: Vec<String> = tags.iter().map(|tag| tag.trim().to_string()).collect();
+    tags.sort();
+    tags.dedup();
+    if tags.len() > MAX_TAGS {
+        return Err(ApiError::InvalidMetadata(format!("at most {} tags are allowed", MAX_TAGS)));
+    }
+    for tag in &tags {
+        let allowed = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':');
+        if tag.is_empty() || tag.len() > MAX_TAG_LEN || !tag.chars().all(allowed) {
+            return Err(ApiError::InvalidMetadata(format!(
+                "tag {:?} must be 1 to {} letters, digits, '-', '_', '.' or ':'",
+                tag, MAX_TAG_LEN
+            )));
+        }
+    }
+    Ok(tags)
+}
+
+/// Checks an owner name: non-empty, at most 128 bytes and without control characters.
+pub fn validate_owner(owner: &str) -> Result<String, ApiError> {
+    let owner = owner.trim();
+    if owner.is_empty() || owner.len() > MAX_OWNER_LEN || owner.chars().any(char::is_control) {
+        return Err(ApiError::InvalidMetadata(format!(
+            "owner must be 1 to {} bytes without control characters",
+            MAX_OWNER_LEN
+        )));
+    }
+    Ok(owner.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(raw: &str) -> &'static str {
+        validate_url(raw, &Settings::default()).unwrap_err().code()
+    }
+
+    #[test]
+    fn accepts_web_urls() {
+        let settings = Settings::default();
+        assert!(validate_url("https://coderprog.com", &settings).is_ok());
+        assert!(validate_url("http://localhost:8080/a?b=c", &settings).is_ok());
+        assert_eq!(validate_url(" HTTPS://Example.com/Path\n", &settings).unwrap(), "HTTPS://Example.com/Path");
+    }
+
+    #[test]
+    fn rejects_everything_else() {
+        assert_eq!(code(""), "empty_url");
+        assert_eq!(code("   "), "empty_url");
+        assert_eq!(code("javascript:alert(1)"), "unsupported_scheme");
+        assert_eq!(code("ftp://example.com/file"), "unsupported_scheme");
+        assert_eq!(code("definitely not a url"), "invalid_url");
+        assert_eq!(code("https://"), "invalid_url");
+        for unsafe_url in ["https://example.com/a\nb", "https://example.com/a\tb", "https://example.com/a b", "https://example.com/\u{7f}"] {
+            assert_eq!(code(unsafe_url), "invalid_url");
+        }
+        assert_eq!(code(&format!("https://example.com/{}", "a".repeat(3000))), "url_too_long");
+    }
+
+    #[test]
+    fn scheme_list_is_configurable() {
+        let settings = Settings {
+            allowed_schemes: vec!["https".to_string()],
+            ..Settings::default()
+        };
+        assert!(validate_url("http://example.com", &settings).is_err());
+        assert!(validate_url("https://example.com", &settings).is_ok());
+    }
+
+    #[test]
+    fn normalizes_tags() {
+        let tags = ["promo", " launch ", "promo"].map(String::from);
+        assert_eq!(validate_tags(&tags).unwrap(), ["launch", "promo"]);
+        assert_eq!(validate_tags(&["team:web".to_string()]).unwrap(), ["team:web"]);
+        for bad in ["", "has space", "a,b", &"x".repeat(33)] {
+            assert_eq!(validate_tags(&[bad.to_string()]).unwrap_err().code(), "invalid_metadata");
+        }
+        let many: Vec<String> = (0..21).map(|i| format!("t{}", i)).collect();
+        assert!(validate_tags(&many).is_err());
+    }
+
+    #[test]
+    fn checks_owner() {
+        assert_eq!(validate_owner(" growth team ").unwrap(), "growth team");
+        assert!(validate_owner("").is_err());
+        assert!(validate_owner("a\nb").is_err());
+    }
+}
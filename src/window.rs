@@ -0,0 +1,48 @@
+//! Time windows accepted by the ranking endpoints, e.g. `?window=24h`.
+
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::store::BUCKET_RETENTION_SECS;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum Window {
+    #[serde(rename = "1h")]
+    Hour,
+    #[serde(rename = "24h")]
+    Day,
+    #[serde(rename = "7d")]
+    Week,
+    /// Lifetime counts.
+    #[default]
+    #[serde(rename = "all")]
+    All,
+}
+
+impl FromStr for Window {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Window, String> {
+        match s {
+            "1h" => Ok(Window::Hour),
+            "24h" => Ok(Window::Day),
+            "7d" => Ok(Window::Week),
+            "all" => Ok(Window::All),
+            other => Err(format!("unknown window {}, expected 1h, 24h, 7d or all", other)),
+        }
+    }
+}
+
+impl Window {
+    /// Start of the window ending at `now`, or `None` for lifetime counts.
+    pub fn since(self, now: u64) -> Option<u64> {
+        let secs = match self {
+            Window::Hour => 3600,
+            Window::Day => 24 * 3600,
+            Window::Week => BUCKET_RETENTION_SECS,
+            Window::All => return None,
+        };
+        Some(now.saturating_sub(secs))
+    }
+}